@@ -1,15 +1,16 @@
-use flapjack;
 use flapjack::flapjack_stack;
 use std::fs;
 
 fn main() {
     // we do this instead of loading a file
     let example_log = read_from_example_file("example_logs/directives/input.flap");
-    let mut foo =
+    let mut builder =
         flapjack_stack::flapjack_stack_builder::FlapJackStackBuilder::new(&example_log, None);
-    let bar = foo.build();
-    bar.serialize_to_file("example_logs/directives/output.flap");
-    println!("{:?}", bar.db);
+    let stack = builder.build().expect("The example log should parse");
+    stack
+        .serialize_to_file("example_logs/directives/output.flap")
+        .unwrap();
+    println!("{:?}", stack.db);
 }
 
 fn read_from_example_file(path: &str) -> String {
@@ -0,0 +1,70 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use chrono::Local;
+
+use super::{build_stack, read_log, Args, CliError};
+use crate::flapjack_stack::checkpoint::archive_hash;
+use crate::import::DATE_FORMAT;
+
+pub(super) fn run(args: &[String]) -> Result<i32, CliError> {
+    let args = Args::parse(args, &["archive"], &[])?;
+
+    let path = args.log_path()?;
+    // an encrypted log is archived as it is, still encrypted
+    let raw = read_log(&path)?;
+    let stack = build_stack(&path, &raw)?;
+    // the checkpoint only hashes the log itself, so it would not cover the included files
+    if stack.files.len() > 1 {
+        return Err(CliError::CompactWithIncludes(path));
+    }
+
+    let log_directory = Path::new(&path).parent().unwrap_or_else(|| Path::new(""));
+    let archive_path = match args.value("archive") {
+        Some(x) => PathBuf::from(x),
+        None => default_archive_path(&path),
+    };
+    let archive_display = archive_path.to_string_lossy().to_string();
+
+    // an earlier archive is never overwritten
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&archive_path)
+        .and_then(|mut file| file.write_all(&raw))
+        .map_err(|e| CliError::CouldNotWriteFile(archive_display.clone(), e))?;
+
+    // the checkpoint names the archive relative to the log when it is next to it
+    let archive_name = match archive_path.strip_prefix(log_directory) {
+        Ok(x) if !log_directory.as_os_str().is_empty() => x.to_string_lossy().to_string(),
+        _ => fs::canonicalize(&archive_path)
+            .map(|x| x.to_string_lossy().to_string())
+            .unwrap_or_else(|_| archive_display.clone()),
+    };
+
+    let mut checkpoint = stack.checkpoint(&archive_name, &archive_hash(&raw));
+    checkpoint
+        .write_file(0)
+        .map_err(|e| CliError::CouldNotWriteFile(path.clone(), e))?;
+
+    println!(
+        "Moved {} lines to {} and started {} from a checkpoint.",
+        stack.serialize().lines().count(),
+        archive_display,
+        path
+    );
+    Ok(0)
+}
+
+/// `log_db.flap` is archived to `log_db.2026-10-19.archive.flap` next to it.
+fn default_archive_path(path: &str) -> PathBuf {
+    let path = Path::new(path);
+    let stem = path
+        .file_stem()
+        .map(|x| x.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let today = Local::now().date_naive().format(DATE_FORMAT);
+
+    path.with_file_name(format!("{}.{}.archive.flap", stem, today))
+}
@@ -0,0 +1,76 @@
+use super::{load_stack, Args, CliError};
+use crate::flapjack_stack::diff::WalletDiff;
+use crate::flapjack_stack::history::Position;
+
+/// With two logs, `--from-line` is a line of the first and `--to-line` a line of the second.
+/// Without any, both lines are in the log given with `--log`.
+pub(super) fn run(args: &[String]) -> Result<i32, CliError> {
+    let args = Args::parse(args, &["from-line", "to-line"], &[])?;
+
+    let (old, new) = match args.positional.as_slice() {
+        [] => {
+            let stack = args.load_stack()?;
+            (stack, None)
+        }
+        [old, new] => (load_stack(old)?, Some(load_stack(new)?)),
+        _ => {
+            return Err(CliError::InvalidArguments(
+                "`diff` compares two logs, or two lines of one log.".to_owned(),
+            ))
+        }
+    };
+    // within one log, the lines default to the whole log
+    let same_log = new.is_none();
+    let new = new.as_ref().unwrap_or(&old);
+
+    let from = match line_option(&args, "from-line")? {
+        Some(line) => old.cutoff(Position::Line(line)),
+        None if same_log => 0,
+        None => old.flapjacks.len(),
+    };
+    let to = match line_option(&args, "to-line")? {
+        Some(line) => new.cutoff(Position::Line(line)),
+        None => new.flapjacks.len(),
+    };
+
+    let diff = old.diff(from, new, to);
+    if diff.is_empty() {
+        println!("No balances changed.");
+    }
+    for wallet in &diff {
+        println!("{}", describe(wallet));
+        for directive in &wallet.directives {
+            let sign = if directive.added { '+' } else { '-' };
+            println!(
+                "  {} {}:{} {}",
+                sign, directive.file, directive.line, directive.text
+            );
+        }
+    }
+    Ok(0)
+}
+
+fn line_option(args: &Args, name: &str) -> Result<Option<usize>, CliError> {
+    match args.value(name) {
+        Some(x) => match x.parse() {
+            Ok(line) => Ok(Some(line)),
+            Err(_) => Err(CliError::InvalidValue(name.to_owned(), x.to_owned())),
+        },
+        None => Ok(None),
+    }
+}
+
+fn describe(wallet: &WalletDiff) -> String {
+    match (wallet.before, wallet.after) {
+        (Some(before), Some(after)) => format!(
+            "{}: {:.2} -> {:.2} ({:+.2})",
+            wallet.wallet,
+            before,
+            after,
+            after - before
+        ),
+        (None, Some(after)) => format!("{}: created, {:.2}", wallet.wallet, after),
+        (Some(before), None) => format!("{}: destroyed, was {:.2}", wallet.wallet, before),
+        (None, None) => format!("{}: created and destroyed", wallet.wallet),
+    }
+}
@@ -0,0 +1,68 @@
+use super::{Args, CliError};
+use crate::encryption::{EncryptionError, EncryptionKey};
+use crate::file_io;
+use crate::flapjack_stack::FlapJackStack;
+
+/// Encrypts the log and every file it includes with a new passphrase.
+pub(super) fn run_encrypt(args: &[String]) -> Result<i32, CliError> {
+    let args = Args::parse(args, &[], &[])?;
+    let mut stack = args.load_stack()?;
+    if stack.files[0].key.is_some() {
+        return Err(EncryptionError::AlreadyEncrypted.into());
+    }
+
+    let passphrase = file_io::read_new_passphrase()?;
+    for file in stack.files.iter_mut() {
+        file.key = Some(EncryptionKey::new(&passphrase)?);
+    }
+    write_files(&mut stack)?;
+
+    println!("Encrypted {}.", args.log_path()?);
+    Ok(0)
+}
+
+/// Writes the log and every file it includes as plain text again.
+pub(super) fn run_decrypt(args: &[String]) -> Result<i32, CliError> {
+    let args = Args::parse(args, &[], &[])?;
+    let mut stack = args.load_stack()?;
+    if stack.files[0].key.is_none() {
+        return Err(EncryptionError::NotEncrypted.into());
+    }
+
+    for file in stack.files.iter_mut() {
+        file.key = None;
+    }
+    write_files(&mut stack)?;
+
+    println!("Decrypted {}.", args.log_path()?);
+    Ok(0)
+}
+
+/// Encrypts the encrypted files again with a new passphrase.
+pub(super) fn run_change_passphrase(args: &[String]) -> Result<i32, CliError> {
+    let args = Args::parse(args, &[], &[])?;
+    let mut stack = args.load_stack()?;
+    if stack.files[0].key.is_none() {
+        return Err(EncryptionError::NotEncrypted.into());
+    }
+
+    let passphrase = file_io::read_new_passphrase()?;
+    for file in stack.files.iter_mut().filter(|x| x.key.is_some()) {
+        file.key = Some(EncryptionKey::new(&passphrase)?);
+    }
+    write_files(&mut stack)?;
+
+    println!("Changed the passphrase.");
+    Ok(0)
+}
+
+fn write_files(stack: &mut FlapJackStack) -> Result<(), CliError> {
+    for i in 0..stack.files.len() {
+        // files made by the builder always have a path
+        let path = stack.files[i].path.clone().unwrap_or_default();
+        stack
+            .write_file(i)
+            .map_err(|e| CliError::CouldNotWriteFile(path, e))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,41 @@
+use super::{Args, CliError};
+use crate::export::beancount::BeancountExporter;
+use crate::export::ledger::LedgerExporter;
+use crate::export::{csv, json};
+
+pub(super) fn run(args: &[String]) -> Result<i32, CliError> {
+    let args = Args::parse(args, &["format", "target", "output"], &[])?;
+
+    let format = match args.value("format") {
+        Some(x) => x,
+        None => return Err(CliError::MissingOption("format".to_owned())),
+    };
+    let target = args.value("target").unwrap_or("balances");
+
+    let stack = args.load_stack()?;
+
+    let exported = match (format, target) {
+        ("json", "balances") => json::balances(&stack),
+        ("json", "log") => json::directives(&stack),
+        ("csv", "balances") => csv::balances(&stack),
+        ("csv", "log") => csv::directives(&stack),
+        // journals always contain the whole log
+        ("ledger", _) => LedgerExporter::default().export(&stack),
+        ("beancount", _) => BeancountExporter::default().export(&stack),
+        ("json", _) | ("csv", _) => {
+            return Err(CliError::InvalidValue(
+                "target".to_owned(),
+                target.to_owned(),
+            ))
+        }
+        _ => {
+            return Err(CliError::InvalidValue(
+                "format".to_owned(),
+                format.to_owned(),
+            ))
+        }
+    };
+
+    args.write_output(&exported)?;
+    Ok(0)
+}
@@ -0,0 +1,39 @@
+use super::{Args, CliError};
+use crate::flapjack_stack::format::FormatOptions;
+
+pub(super) fn run(args: &[String]) -> Result<i32, CliError> {
+    let args = Args::parse(args, &[], &["check", "sort-creates"])?;
+
+    let mut stack = args.load_stack()?;
+    let before = (0..stack.files.len())
+        .map(|file| stack.serialize_file(file))
+        .collect::<Vec<String>>();
+
+    stack.format(FormatOptions {
+        sort_creates: args.switch("sort-creates"),
+    });
+
+    let mut unformatted = 0;
+    for (file, before) in before.iter().enumerate() {
+        let formatted = stack.serialize_file(file);
+        if formatted == *before {
+            continue;
+        }
+        unformatted += 1;
+
+        // files made by the builder always have a path
+        let path = stack.files[file].path.clone().unwrap_or_default();
+        if args.switch("check") {
+            println!("{} is not formatted. Run `flapjack fmt` to fix it.", path);
+        } else {
+            stack
+                .write_file(file)
+                .map_err(|e| CliError::CouldNotWriteFile(path, e))?;
+        }
+    }
+
+    if args.switch("check") && unformatted > 0 {
+        return Ok(1);
+    }
+    Ok(0)
+}
@@ -0,0 +1,43 @@
+use super::{load_stack, Args, CliError};
+use crate::encryption;
+use crate::flapjack_stack::merge;
+use crate::flapjack_stack::store::{FileStore, LogStore};
+
+/// Writes the merged log over OURS unless `--output` is given, the way git expects of a merge driver.
+/// Returns 1 if there were conflicts.
+pub(super) fn run(args: &[String]) -> Result<i32, CliError> {
+    let args = Args::parse(args, &["output"], &[])?;
+
+    let (base, ours, theirs) = match args.positional.as_slice() {
+        [base, ours, theirs] => (base, ours, theirs),
+        _ => {
+            return Err(CliError::InvalidArguments(
+                "`merge` needs the base log, our log and their log.".to_owned(),
+            ))
+        }
+    };
+
+    let our_stack = load_stack(ours)?;
+    let merged = merge::merge(&load_stack(base)?, &our_stack, &load_stack(theirs)?)?;
+
+    // an encrypted log stays encrypted
+    let bytes = match &our_stack.files[0].key {
+        Some(key) => encryption::encrypt(&merged.log, key),
+        None => merged.log.into_bytes(),
+    };
+    let output = args.value("output").unwrap_or(ours);
+    FileStore::new(output)
+        .rewrite(&bytes)
+        .map_err(|e| CliError::CouldNotWriteFile(output.to_owned(), e))?;
+
+    if merged.conflicts.is_empty() {
+        println!("Merged into {}.", output);
+        return Ok(0);
+    }
+    println!(
+        "Merged into {} with conflicts in {}. Resolve them before using the log.",
+        output,
+        merged.conflicts.join(", ")
+    );
+    Ok(1)
+}
@@ -0,0 +1,244 @@
+use std::collections::HashMap;
+use std::fs;
+
+use crate::encryption::EncryptionError;
+use crate::file_io::{self, InitLogDbError, PassphraseError};
+use crate::flapjack_stack::flapjack::ParseError;
+use crate::flapjack_stack::flapjack_stack_builder::FlapJackStackBuilder;
+use crate::flapjack_stack::merge::MergeError;
+use crate::flapjack_stack::{FlapJackStack, PushError};
+
+mod compact_command;
+mod diff_command;
+mod encryption_command;
+mod export_command;
+mod fmt_command;
+mod merge_command;
+mod schedule_command;
+#[cfg(feature = "sqlite")]
+mod sqlite_command;
+mod verify_command;
+mod view_command;
+
+pub const USAGE: &str = "Usage: flapjack [COMMAND] [OPTIONS]
+
+Running flapjack without a command starts the interactive menu.
+
+Commands:
+    change-passphrase
+    compact [--archive <FILE>]
+    decrypt
+    diff [<OLD> <NEW>] [--from-line <N>] [--to-line <N>]
+    encrypt
+    export --format <json|csv|ledger|beancount> [--target <balances|log>] [--output <FILE>]
+    fmt [--check] [--sort-creates]
+    merge <BASE> <OURS> <THEIRS> [--output <FILE>]
+    run-schedule
+    sqlite [--database <FILE>] [--plaintext]    (only with the sqlite feature)
+    verify
+    view [--as-of <DATE> | --at-line <N>] [--group <GROUP>]
+    help
+
+Options for every command:
+    --log <FILE>    Use this log instead of the default log_db.flap
+
+Encrypted logs are read with the passphrase in FLAPJACK_PASSPHRASE, or one that is asked for.
+New passphrases are read from FLAPJACK_NEW_PASSPHRASE in the same way.";
+
+#[non_exhaustive]
+#[derive(thiserror::Error, Debug)]
+pub enum CliError {
+    #[error("Unknown command \"{0}\". Run `flapjack help` to see the available commands.")]
+    UnknownCommand(String),
+    #[error("Unknown option \"{0}\".")]
+    UnknownOption(String),
+    #[error("Option \"--{0}\" needs a value.")]
+    MissingValue(String),
+    #[error("Option \"--{0}\" is required.")]
+    MissingOption(String),
+    #[error("Invalid value \"{1}\" for option \"--{0}\".")]
+    InvalidValue(String, String),
+    #[error("{0}")]
+    InvalidArguments(String),
+    #[error("Options \"--{0}\" and \"--{1}\" can not be used together.")]
+    ConflictingOptions(String, String),
+    #[error("Could not read {0}. ({1})")]
+    CouldNotReadFile(String, #[source] std::io::Error),
+    #[error("Could not write {0}. ({1})")]
+    CouldNotWriteFile(String, #[source] std::io::Error),
+    #[error("Can not compact {0} because it includes other files. Move their lines into it first, so the archive holds the whole history.")]
+    CompactWithIncludes(String),
+    #[error(transparent)]
+    InitLogDb(#[from] InitLogDbError),
+    #[error(transparent)]
+    Parse(#[from] ParseError),
+    #[error(transparent)]
+    Merge(#[from] MergeError),
+    #[error(transparent)]
+    Encryption(#[from] EncryptionError),
+    #[error(transparent)]
+    Passphrase(#[from] PassphraseError),
+    #[error(transparent)]
+    Push(#[from] PushError),
+    #[cfg(feature = "sqlite")]
+    #[error("SQLite error. ({0})")]
+    Sqlite(#[from] rusqlite::Error),
+}
+
+/// Runs the command named by the first argument and returns the exit code.
+pub fn run(args: &[String]) -> Result<i32, CliError> {
+    let (command, rest) = match args.split_first() {
+        Some(x) => x,
+        None => {
+            println!("{}", USAGE);
+            return Ok(0);
+        }
+    };
+
+    match command.as_str() {
+        "change-passphrase" => encryption_command::run_change_passphrase(rest),
+        "compact" => compact_command::run(rest),
+        "decrypt" => encryption_command::run_decrypt(rest),
+        "diff" => diff_command::run(rest),
+        "encrypt" => encryption_command::run_encrypt(rest),
+        "export" => export_command::run(rest),
+        "fmt" => fmt_command::run(rest),
+        "merge" => merge_command::run(rest),
+        "run-schedule" => schedule_command::run(rest),
+        #[cfg(feature = "sqlite")]
+        "sqlite" => sqlite_command::run(rest),
+        "verify" => verify_command::run(rest),
+        "view" => view_command::run(rest),
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            Ok(0)
+        }
+        _ => Err(CliError::UnknownCommand(command.clone())),
+    }
+}
+
+/// The parsed arguments of a single command.
+#[derive(Debug, PartialEq, Default)]
+pub struct Args {
+    pub positional: Vec<String>,
+    values: HashMap<String, String>,
+    switches: Vec<String>,
+}
+
+impl Args {
+    /// Options listed in `value_options` take a value (`--format json` or `--format=json`),
+    /// options listed in `switch_options` do not. Any other option is an error.
+    /// `--log` is accepted by every command.
+    pub fn parse(
+        args: &[String],
+        value_options: &[&str],
+        switch_options: &[&str],
+    ) -> Result<Self, CliError> {
+        let mut parsed = Self::default();
+        let mut iter = args.iter();
+
+        while let Some(arg) = iter.next() {
+            let option = match arg.strip_prefix("--") {
+                Some(x) => x,
+                None => {
+                    parsed.positional.push(arg.clone());
+                    continue;
+                }
+            };
+
+            let (name, inline_value) = match option.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_owned())),
+                None => (option, None),
+            };
+
+            if name == "log" || value_options.contains(&name) {
+                let value = match inline_value.or_else(|| iter.next().cloned()) {
+                    Some(x) => x,
+                    None => return Err(CliError::MissingValue(name.to_owned())),
+                };
+                parsed.values.insert(name.to_owned(), value);
+            } else if switch_options.contains(&name) && inline_value.is_none() {
+                parsed.switches.push(name.to_owned());
+            } else {
+                return Err(CliError::UnknownOption(arg.clone()));
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(|x| x.as_str())
+    }
+
+    pub fn switch(&self, name: &str) -> bool {
+        self.switches.iter().any(|x| x == name)
+    }
+
+    /// The log given with `--log`, or the default log_db.flap.
+    pub fn log_path(&self) -> Result<String, CliError> {
+        match self.value("log") {
+            Some(x) => Ok(x.to_owned()),
+            None => Ok(file_io::init_log_db()?),
+        }
+    }
+
+    pub fn load_stack(&self) -> Result<FlapJackStack, CliError> {
+        load_stack(&self.log_path()?)
+    }
+
+    /// Writes to the file given with `--output`, or to stdout.
+    pub fn write_output(&self, content: &str) -> Result<(), CliError> {
+        match self.value("output") {
+            Some(path) => fs::write(path, content)
+                .map_err(|e| CliError::CouldNotWriteFile(path.to_owned(), e)),
+            None => {
+                print!("{}", content);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Reads the log, asking for the passphrase if it is encrypted.
+pub fn load_stack(path: &str) -> Result<FlapJackStack, CliError> {
+    let bytes = read_log(path)?;
+    build_stack(path, &bytes)
+}
+
+fn read_log(path: &str) -> Result<Vec<u8>, CliError> {
+    fs::read(path).map_err(|e| CliError::CouldNotReadFile(path.to_owned(), e))
+}
+
+fn build_stack(path: &str, bytes: &[u8]) -> Result<FlapJackStack, CliError> {
+    let passphrase = file_io::passphrase_for(path, bytes)?;
+    let mut builder =
+        FlapJackStackBuilder::from_bytes(bytes, Some(path.to_owned()), passphrase.as_deref())?;
+    Ok(builder.build()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Args;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|x| x.to_string()).collect()
+    }
+
+    #[test]
+    fn test_args_parse() {
+        let args = strings(&["a.flap", "--format=json", "--check", "--log", "b.flap"]);
+        let parsed = Args::parse(&args, &["format"], &["check"]).unwrap();
+
+        assert_eq!(parsed.positional, vec!["a.flap".to_owned()]);
+        assert_eq!(parsed.value("format"), Some("json"));
+        assert_eq!(parsed.value("log"), Some("b.flap"));
+        assert!(parsed.switch("check"));
+    }
+
+    #[test]
+    fn test_args_reject_unknown_option() {
+        let args = strings(&["--frmat", "json"]);
+        assert!(Args::parse(&args, &["format"], &[]).is_err());
+    }
+}
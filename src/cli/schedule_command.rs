@@ -0,0 +1,25 @@
+use chrono::Local;
+
+use super::{Args, CliError};
+
+pub(super) fn run(args: &[String]) -> Result<i32, CliError> {
+    let args = Args::parse(args, &[], &[])?;
+    let mut stack = args.load_stack()?;
+
+    let run = stack.run_schedule(Local::now().date_naive())?;
+    if run.added.is_empty() && run.blocked.is_empty() {
+        println!("Nothing is due.");
+    }
+    for occurrence in &run.added {
+        println!("Added {}", occurrence.flapjack.serialize());
+    }
+    for (_, overdraft) in &run.warnings {
+        println!("Warning: {}", overdraft);
+    }
+    for (occurrence, overdraft) in &run.blocked {
+        println!("Skipped {}: {}", occurrence.flapjack.serialize(), overdraft);
+    }
+
+    // skipped occurrences are due again next time, so a scheduled run can notice them
+    Ok(if run.blocked.is_empty() { 0 } else { 1 })
+}
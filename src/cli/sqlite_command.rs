@@ -0,0 +1,38 @@
+use std::path::Path;
+
+use rusqlite::Connection;
+
+use super::{Args, CliError};
+use crate::export::sqlite;
+
+pub(super) fn run(args: &[String]) -> Result<i32, CliError> {
+    let args = Args::parse(args, &["database"], &["plaintext"])?;
+
+    let path = args.log_path()?;
+    let stack = args.load_stack()?;
+
+    // the database is not encrypted, so copying an encrypted log into it has to be asked for
+    if stack.files.iter().any(|x| x.key.is_some()) && !args.switch("plaintext") {
+        return Err(CliError::InvalidArguments(
+            "The log is encrypted, but the database would not be. Add --plaintext to copy it anyway."
+                .to_owned(),
+        ));
+    }
+
+    // log_db.flap is copied to log_db.sqlite next to it
+    let database = match args.value("database") {
+        Some(x) => x.to_owned(),
+        None => Path::new(&path)
+            .with_extension("sqlite")
+            .to_string_lossy()
+            .to_string(),
+    };
+
+    let mut connection = Connection::open(&database)?;
+    if sqlite::sync(&stack, &mut connection)? {
+        println!("Rebuilt {} from {}.", database, path);
+    } else {
+        println!("{} is up to date.", database);
+    }
+    Ok(0)
+}
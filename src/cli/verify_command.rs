@@ -0,0 +1,17 @@
+use super::{Args, CliError};
+
+pub(super) fn run(args: &[String]) -> Result<i32, CliError> {
+    let args = Args::parse(args, &[], &[])?;
+    let stack = args.load_stack()?;
+
+    match stack.verify() {
+        Ok(checked) => {
+            println!("Checked {} hashes. Nothing has been changed.", checked);
+            Ok(0)
+        }
+        Err(e) => {
+            println!("{}", e);
+            Ok(1)
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use chrono::{Local, NaiveDate};
+
+use super::{Args, CliError};
+use crate::flapjack_stack::history::Position;
+use crate::import::DATE_FORMAT;
+use crate::option_repl::{balance_table, goal_table};
+
+pub(super) fn run(args: &[String]) -> Result<i32, CliError> {
+    let args = Args::parse(args, &["as-of", "at-line", "group"], &[])?;
+    let group = args.value("group");
+
+    let position = match (args.value("as-of"), args.value("at-line")) {
+        (Some(_), Some(_)) => {
+            return Err(CliError::ConflictingOptions(
+                "as-of".to_owned(),
+                "at-line".to_owned(),
+            ))
+        }
+        (Some(date), None) => match NaiveDate::parse_from_str(date, DATE_FORMAT) {
+            Ok(x) => Some(Position::Date(x)),
+            Err(_) => return Err(CliError::InvalidValue("as-of".to_owned(), date.to_owned())),
+        },
+        (None, Some(line)) => match line.parse() {
+            Ok(x) => Some(Position::Line(x)),
+            Err(_) => {
+                return Err(CliError::InvalidValue(
+                    "at-line".to_owned(),
+                    line.to_owned(),
+                ))
+            }
+        },
+        (None, None) => None,
+    };
+
+    let stack = args.load_stack()?;
+    let earlier;
+    let db = match position {
+        Some(position) => {
+            earlier = stack.db_at(position);
+            &earlier
+        }
+        None => &stack.db,
+    };
+    if let Some(group) = group.filter(|x| db.wallet_names_in(x).is_empty()) {
+        return Err(CliError::InvalidValue("group".to_owned(), group.to_owned()));
+    }
+
+    let mut table = balance_table(db, group);
+    // goals are about every wallet as it is now
+    if position.is_none() && group.is_none() {
+        table.extend(goal_table(&stack, Local::now().date_naive()));
+    }
+
+    print!("{}", table);
+    Ok(0)
+}
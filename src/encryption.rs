@@ -0,0 +1,165 @@
+//! Encrypted logs.
+//!
+//! An encrypted log starts with `MAGIC`, followed by the salt the key was derived with, the nonce
+//! and the XChaCha20-Poly1305 ciphertext. The key is derived from the passphrase with Argon2id.
+//! The header is authenticated along with the log, so changing any byte of the file is caught.
+
+use std::fmt;
+
+use argon2::Argon2;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+
+pub const MAGIC: &[u8] = b"FLAPJACK ENCRYPTED 1\n";
+
+/// Read by `FlapJackStackBuilder::from_file` and the command line when a log is encrypted.
+pub const PASSPHRASE_VAR: &str = "FLAPJACK_PASSPHRASE";
+
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 24;
+const KEY_LENGTH: usize = 32;
+
+#[derive(thiserror::Error, Debug, PartialEq, Clone)]
+pub enum EncryptionError {
+    #[error("The log is encrypted. A passphrase is needed to read it.")]
+    MissingPassphrase,
+    #[error("The passphrase is wrong, or the log has been damaged.")]
+    WrongPassphrase,
+    #[error("The log is not encrypted.")]
+    NotEncrypted,
+    #[error("The log is already encrypted.")]
+    AlreadyEncrypted,
+    #[error("The encrypted log is too short to be read.")]
+    Truncated,
+    #[error("The log is not valid text.")]
+    InvalidText,
+    #[error("Could not derive a key from the passphrase. ({0})")]
+    KeyDerivation(String),
+}
+
+/// A key derived from a passphrase, together with the salt it was derived with.
+/// Kept by a `FlapJackStack` so that writes can be encrypted without asking for the passphrase again.
+#[derive(Clone, PartialEq)]
+pub struct EncryptionKey {
+    salt: [u8; SALT_LENGTH],
+    key: [u8; KEY_LENGTH],
+}
+
+impl EncryptionKey {
+    /// Derives a key with a new random salt.
+    pub fn new(passphrase: &str) -> Result<Self, EncryptionError> {
+        let mut salt = [0; SALT_LENGTH];
+        OsRng.fill_bytes(&mut salt);
+        Self::derive(passphrase, salt)
+    }
+
+    fn derive(passphrase: &str, salt: [u8; SALT_LENGTH]) -> Result<Self, EncryptionError> {
+        let mut key = [0; KEY_LENGTH];
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), &salt, &mut key)
+            .map_err(|e| EncryptionError::KeyDerivation(e.to_string()))?;
+
+        Ok(Self { salt, key })
+    }
+}
+
+// the key is never printed
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("EncryptionKey").finish_non_exhaustive()
+    }
+}
+
+pub fn is_encrypted(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+/// Encrypts a log with a new nonce.
+pub fn encrypt(log: &str, key: &EncryptionKey) -> Vec<u8> {
+    let cipher = XChaCha20Poly1305::new(&key.key.into());
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+
+    let mut header = MAGIC.to_vec();
+    header.extend_from_slice(&key.salt);
+    header.extend_from_slice(&nonce);
+
+    let payload = Payload {
+        msg: log.as_bytes(),
+        aad: &header,
+    };
+    let ciphertext = cipher
+        .encrypt(&nonce, payload)
+        .expect("Encrypting in memory does not fail");
+
+    header.extend(ciphertext);
+    header
+}
+
+/// Decrypts a log, and returns it with the key that was derived from the passphrase.
+pub fn decrypt(bytes: &[u8], passphrase: &str) -> Result<(String, EncryptionKey), EncryptionError> {
+    if !is_encrypted(bytes) {
+        return Err(EncryptionError::NotEncrypted);
+    }
+
+    let header_length = MAGIC.len() + SALT_LENGTH + NONCE_LENGTH;
+    if bytes.len() < header_length {
+        return Err(EncryptionError::Truncated);
+    }
+    let (header, ciphertext) = bytes.split_at(header_length);
+
+    let mut salt = [0; SALT_LENGTH];
+    salt.copy_from_slice(&header[MAGIC.len()..MAGIC.len() + SALT_LENGTH]);
+    let nonce = XNonce::from_slice(&header[MAGIC.len() + SALT_LENGTH..]);
+
+    let key = EncryptionKey::derive(passphrase, salt)?;
+    let cipher = XChaCha20Poly1305::new(&key.key.into());
+    let payload = Payload {
+        msg: ciphertext,
+        aad: header,
+    };
+    let log = cipher
+        .decrypt(nonce, payload)
+        .map_err(|_| EncryptionError::WrongPassphrase)?;
+    let log = String::from_utf8(log).map_err(|_| EncryptionError::InvalidText)?;
+
+    Ok((log, key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decrypt, encrypt, is_encrypted, EncryptionError, EncryptionKey};
+
+    #[test]
+    fn test_round_trip() {
+        let key = EncryptionKey::new("correct horse").unwrap();
+        let encrypted = encrypt("CREATE Cash\n", &key);
+
+        assert!(is_encrypted(&encrypted));
+        let (log, decrypted_key) = decrypt(&encrypted, "correct horse").unwrap();
+        assert_eq!(log, "CREATE Cash\n");
+        assert_eq!(decrypted_key, key);
+
+        assert_eq!(
+            decrypt(&encrypted, "battery staple"),
+            Err(EncryptionError::WrongPassphrase)
+        );
+    }
+
+    #[test]
+    fn test_tampering_is_caught() {
+        let key = EncryptionKey::new("correct horse").unwrap();
+        let mut encrypted = encrypt("CREATE Cash\n", &key);
+        let last = encrypted.len() - 1;
+        encrypted[last] ^= 1;
+
+        assert_eq!(
+            decrypt(&encrypted, "correct horse"),
+            Err(EncryptionError::WrongPassphrase)
+        );
+        assert_eq!(
+            decrypt(b"CREATE Cash", "correct horse"),
+            Err(EncryptionError::NotEncrypted)
+        );
+    }
+}
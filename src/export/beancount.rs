@@ -0,0 +1,355 @@
+use chrono::{Duration, NaiveDate};
+use std::collections::{HashMap, HashSet};
+
+use super::dated_directives;
+use crate::flapjack_stack::flapjack::{Directive, FlapJack};
+use crate::flapjack_stack::group::GROUP_SEPARATOR;
+use crate::flapjack_stack::FlapJackStack;
+use crate::import::DATE_FORMAT;
+
+/// Converts a `FlapJackStack` into a Beancount ledger.
+///
+/// CREATE and DESTROY become `open` and `close`, INCREMENT and DECREMENT become
+/// transactions against a counter-account, and SET becomes a `pad` followed by a
+/// `balance` assertion on the next day (Beancount checks balances at the start of a day).
+#[derive(Debug, Clone)]
+pub struct BeancountExporter {
+    pub currency: String,
+    /// Prepended to every wallet name. Must be one of Beancount's root account types.
+    pub wallet_prefix: String,
+    /// The other side of an INCREMENT.
+    pub income_account: String,
+    /// The other side of a DECREMENT.
+    pub expense_account: String,
+    /// The account a SET pads from.
+    pub adjustment_account: String,
+}
+
+impl Default for BeancountExporter {
+    fn default() -> Self {
+        Self {
+            currency: "USD".to_owned(),
+            wallet_prefix: "Assets".to_owned(),
+            income_account: "Equity:Unknown".to_owned(),
+            expense_account: "Equity:Unknown".to_owned(),
+            adjustment_account: "Equity:Unknown".to_owned(),
+        }
+    }
+}
+
+// A SET that still needs its pad and balance written once its day is over.
+struct PendingBalance {
+    wallet: String,
+    // How far the SETs of the day moved the balance away from the transactions.
+    adjustment: f64,
+}
+
+// The account of every open wallet. Beancount can not open an account again once it is closed,
+// and different wallets can be sanitized to the same name, so no account name is given out twice.
+#[derive(Default)]
+struct Accounts {
+    open: HashMap<String, String>,
+    used: HashSet<String>,
+}
+
+impl Accounts {
+    // opens a new account for the wallet, with a number after its name if the name was taken
+    fn open(&mut self, exporter: &BeancountExporter, wallet: &str) -> String {
+        let name = exporter.wallet_account(wallet);
+        let mut account = name.clone();
+        let mut number = 2;
+        while self.used.contains(&account) {
+            account = format!("{}-{}", name, number);
+            number += 1;
+        }
+
+        self.used.insert(account.clone());
+        self.open.insert(wallet.to_owned(), account.clone());
+        account
+    }
+
+    // a wallet used without a CREATE still gets an account of its own
+    fn get(&mut self, exporter: &BeancountExporter, wallet: &str) -> String {
+        match self.open.get(wallet) {
+            Some(account) => account.clone(),
+            None => self.open(exporter, wallet),
+        }
+    }
+}
+
+impl BeancountExporter {
+    pub fn export(&self, stack: &FlapJackStack) -> String {
+        let dated = dated_directives(stack);
+        let mut entries: Vec<String> = Vec::new();
+        let mut accounts = Accounts::default();
+
+        entries.push(format!(
+            "option \"operating_currency\" \"{}\"",
+            self.currency
+        ));
+
+        if let Some(first) = dated.first() {
+            let mut counter_accounts = vec![
+                &self.income_account,
+                &self.expense_account,
+                &self.adjustment_account,
+            ];
+            counter_accounts.sort();
+            counter_accounts.dedup();
+
+            for account in counter_accounts {
+                accounts.used.insert(account.clone());
+                entries.push(format!(
+                    "{} open {}",
+                    first.date.format(DATE_FORMAT),
+                    account
+                ));
+            }
+        }
+
+        let mut balances: HashMap<String, f64> = HashMap::new();
+        let mut pending: Vec<PendingBalance> = Vec::new();
+        let mut current_day: Option<NaiveDate> = None;
+        let mut dated = dated.into_iter();
+
+        for flapjack in &stack.flapjacks {
+            let dated = match flapjack {
+                FlapJack::Blank => continue,
+                FlapJack::Comment(comment) => {
+                    let text = comment.string.trim_start().trim_start_matches('#').trim();
+                    entries.push(format!("; {}", text));
+                    continue;
+                }
+                FlapJack::Directive(_) => dated.next().expect("Every directive has a date"),
+            };
+
+            if let Some(day) = current_day.filter(|day| *day != dated.date) {
+                self.flush_pending(day, &mut pending, &balances, &accounts, &mut entries);
+            }
+            current_day = Some(dated.date);
+
+            let date = dated.date.format(DATE_FORMAT);
+
+            match dated.directive {
+                // a wallet that is created again while it is open starts over at zero, like a SET
+                Directive::Create { wallet, .. } if accounts.open.contains_key(wallet) => {
+                    set_balance(wallet, 0.0, &mut balances, &mut pending);
+                }
+                Directive::Create { wallet, .. } => {
+                    balances.insert(wallet.clone(), 0.0);
+                    entries.push(format!(
+                        "{} open {} {}",
+                        date,
+                        accounts.open(self, wallet),
+                        self.currency
+                    ));
+                }
+                Directive::Increment { wallet, amount, .. }
+                | Directive::Decrement { wallet, amount, .. } => {
+                    let (signed, counter) = match dated.directive {
+                        Directive::Increment { .. } => (amount.value(), &self.income_account),
+                        _ => (-amount.value(), &self.expense_account),
+                    };
+                    *balances.entry(wallet.clone()).or_insert(0.0) += signed;
+
+                    let narration = dated.description.unwrap_or_default();
+                    entries.push(format!(
+                        "{} * \"{}\"\n  {}  {:.2} {}\n  {}",
+                        date,
+                        escape(&narration),
+                        accounts.get(self, wallet),
+                        signed,
+                        self.currency,
+                        counter
+                    ));
+                }
+                Directive::Set { wallet, amount, .. } => {
+                    accounts.get(self, wallet);
+                    set_balance(wallet, amount.value(), &mut balances, &mut pending);
+                }
+                Directive::Destroy { wallet, .. } => {
+                    // a balance after the close would be rejected
+                    pending.retain(|x| &x.wallet != wallet);
+                    balances.remove(wallet);
+                    let account = accounts.get(self, wallet);
+                    accounts.open.remove(wallet);
+                    entries.push(format!("{} close {}", date, account));
+                }
+                // directives beancount has no equivalent for are kept as comments
+                other @ (Directive::Include { .. }
+                | Directive::Checkpoint { .. }
+                | Directive::Chain
+                | Directive::Recur { .. }
+                | Directive::Budget { .. }
+                | Directive::Goal { .. }
+                | Directive::Policy { .. }
+                | Directive::Meta { .. }
+                | Directive::Other { .. }) => {
+                    entries.push(format!("; {}", other.serialize()));
+                }
+            }
+        }
+
+        if let Some(day) = current_day {
+            self.flush_pending(day, &mut pending, &balances, &accounts, &mut entries);
+        }
+
+        let mut ledger = entries.join("\n\n");
+        ledger.push('\n');
+        ledger
+    }
+
+    fn flush_pending(
+        &self,
+        day: NaiveDate,
+        pending: &mut Vec<PendingBalance>,
+        balances: &HashMap<String, f64>,
+        accounts: &Accounts,
+        entries: &mut Vec<String>,
+    ) {
+        let next_day = day + Duration::days(1);
+
+        for set in pending.drain(..) {
+            let account = &accounts.open[&set.wallet];
+            let balance = balances.get(&set.wallet).copied().unwrap_or(0.0);
+
+            // Beancount rejects a pad that ends up not being needed
+            if format!("{:.2}", set.adjustment.abs()) != "0.00" {
+                entries.push(format!(
+                    "{} pad {} {}",
+                    day.format(DATE_FORMAT),
+                    account,
+                    self.adjustment_account
+                ));
+            }
+
+            entries.push(format!(
+                "{} balance {}  {:.2} {}",
+                next_day.format(DATE_FORMAT),
+                account,
+                balance,
+                self.currency
+            ));
+        }
+    }
+
+    /// Beancount account components must start with a capital letter or a digit and may only
+    /// contain letters, digits and dashes, so `Checking (Bank)` becomes `Checking-Bank`.
+    /// Letters outside ASCII are kept, so `épargne` becomes `Épargne`.
+    /// Each group of a wallet like `Bank:Checking` is its own component.
+    fn wallet_account(&self, wallet: &str) -> String {
+        let components = wallet
+            .split(GROUP_SEPARATOR)
+            .map(|part| {
+                let words = part
+                    .split(|c: char| !c.is_alphanumeric())
+                    .filter(|word| !word.is_empty())
+                    .map(|word| {
+                        let mut chars = word.chars();
+                        let first = chars.next().unwrap().to_uppercase();
+                        format!("{}{}", first, chars.as_str())
+                    })
+                    .collect::<Vec<String>>();
+
+                if words.is_empty() {
+                    "Wallet".to_owned()
+                } else {
+                    words.join("-")
+                }
+            })
+            .collect::<Vec<String>>();
+
+        format!("{}:{}", self.wallet_prefix, components.join(":"))
+    }
+}
+
+// moves a wallet to `amount`, to be asserted once the day is over
+fn set_balance(
+    wallet: &str,
+    amount: f64,
+    balances: &mut HashMap<String, f64>,
+    pending: &mut Vec<PendingBalance>,
+) {
+    let previous = balances.insert(wallet.to_owned(), amount).unwrap_or(0.0);
+
+    match pending.iter_mut().find(|x| x.wallet == wallet) {
+        Some(x) => x.adjustment += amount - previous,
+        None => pending.push(PendingBalance {
+            wallet: wallet.to_owned(),
+            adjustment: amount - previous,
+        }),
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BeancountExporter;
+    use crate::flapjack_stack::flapjack_stack_builder::FlapJackStackBuilder;
+
+    #[test]
+    fn test_wallet_account_is_sanitized() {
+        let exporter = BeancountExporter::default();
+        assert_eq!(
+            exporter.wallet_account("Checking (Bank)"),
+            "Assets:Checking-Bank"
+        );
+        assert_eq!(exporter.wallet_account("cash"), "Assets:Cash");
+        assert_eq!(exporter.wallet_account("(?)"), "Assets:Wallet");
+        assert_eq!(
+            exporter.wallet_account("bank:joint savings"),
+            "Assets:Bank:Joint-Savings"
+        );
+        assert_eq!(
+            exporter.wallet_account("épargne (Crédit Agricole)"),
+            "Assets:Épargne-Crédit-Agricole"
+        );
+    }
+
+    // the entries that open or close an account
+    fn opens_and_closes(log: &str) -> Vec<String> {
+        let stack = FlapJackStackBuilder::new(log, None).build().unwrap();
+        BeancountExporter::default()
+            .export(&stack)
+            .split("\n\n")
+            .filter(|x| x.contains(" open Assets") || x.contains(" close "))
+            .map(|x| x.trim_end().to_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_wallets_with_the_same_account_name() {
+        let log = "CREATE \"Checking (Bank)\" \"2026-09-01\"\nCREATE checking-bank\n";
+        assert_eq!(
+            opens_and_closes(log),
+            vec![
+                "2026-09-01 open Assets:Checking-Bank USD",
+                "2026-09-01 open Assets:Checking-Bank-2 USD",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_wallet_created_again() {
+        let log = "CREATE Cash \"2026-09-01\"\nINCREMENT Cash 5\nDESTROY Cash \"2026-09-02\"\n\
+                   CREATE Cash \"2026-09-03\"\nINCREMENT Cash 2\n";
+        assert_eq!(
+            opens_and_closes(log),
+            vec![
+                "2026-09-01 open Assets:Cash USD",
+                "2026-09-02 close Assets:Cash",
+                "2026-09-03 open Assets:Cash-2 USD",
+            ]
+        );
+
+        // created again while it is open, it starts over at zero
+        let log = "CREATE Cash \"2026-09-01\"\nINCREMENT Cash 5\nCREATE Cash \"2026-09-02\"\n";
+        let stack = FlapJackStackBuilder::new(log, None).build().unwrap();
+        let exported = BeancountExporter::default().export(&stack);
+        assert_eq!(exported.matches(" open Assets:Cash").count(), 1);
+        assert!(exported.contains("2026-09-03 balance Assets:Cash  0.00 USD"));
+    }
+}
@@ -0,0 +1,62 @@
+use super::directive_rows;
+use crate::flapjack_stack::FlapJackStack;
+
+/// Exports the current balance of every wallet, sorted by wallet name.
+pub fn balances(stack: &FlapJackStack) -> String {
+    let mut csv = String::from("wallet,amount\n");
+
+    for name in stack.return_wallet_names() {
+        let amount = stack.amount(&name);
+        csv.push_str(&format!("{},{:.2}\n", field(&name), amount));
+    }
+
+    csv
+}
+
+/// Exports every directive in the log with the line it was read from.
+pub fn directives(stack: &FlapJackStack) -> String {
+    let mut csv = String::from("line,command,wallet,amount,comment\n");
+
+    for row in directive_rows(stack) {
+        let amount = row.amount.map(|x| format!("{:.2}", x)).unwrap_or_default();
+        let wallet = row.wallet.as_deref().map(field).unwrap_or_default();
+        let comment = row.comment.as_deref().map(field).unwrap_or_default();
+
+        csv.push_str(&format!(
+            "{},{},{},{},{}\n",
+            row.line, row.command, wallet, amount, comment
+        ));
+    }
+
+    csv
+}
+
+// quotes a field if it would otherwise break the row (RFC 4180)
+fn field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::flapjack_stack::flapjack_stack_builder::FlapJackStackBuilder;
+
+    #[test]
+    fn test_csv_directives() {
+        let log = "CREATE \"Checking (Bank)\"
+INCREMENT \"Checking (Bank)\" 50 \"paid, finally\"
+DESTROY \"Checking (Bank)\"";
+
+        let stack = FlapJackStackBuilder::new(log, None).build().unwrap();
+        let expected = "line,command,wallet,amount,comment
+1,CREATE,Checking (Bank),,
+2,INCREMENT,Checking (Bank),50.00,\"paid, finally\"
+3,DESTROY,Checking (Bank),,
+";
+
+        assert_eq!(super::directives(&stack), expected);
+    }
+}
@@ -0,0 +1,97 @@
+use super::directive_rows;
+use crate::flapjack_stack::FlapJackStack;
+
+/// Exports the current balance of every wallet, sorted by wallet name.
+pub fn balances(stack: &FlapJackStack) -> String {
+    let wallets = stack
+        .return_wallet_names()
+        .iter()
+        .map(|name| {
+            format!(
+                "    {{\"wallet\": {}, \"amount\": {}}}",
+                string(name),
+                number(Some(stack.amount(name)))
+            )
+        })
+        .collect::<Vec<String>>();
+
+    array(wallets)
+}
+
+/// Exports every directive in the log with the line it was read from.
+pub fn directives(stack: &FlapJackStack) -> String {
+    let rows = directive_rows(stack)
+        .iter()
+        .map(|row| {
+            format!(
+                "    {{\"line\": {}, \"command\": {}, \"wallet\": {}, \"amount\": {}, \"comment\": {}}}",
+                row.line,
+                string(&row.command),
+                row.wallet.as_deref().map_or("null".to_owned(), string),
+                number(row.amount),
+                row.comment.as_deref().map_or("null".to_owned(), string)
+            )
+        })
+        .collect::<Vec<String>>();
+
+    array(rows)
+}
+
+fn array(items: Vec<String>) -> String {
+    if items.is_empty() {
+        return "[]\n".to_owned();
+    }
+
+    format!("[\n{}\n]\n", items.join(",\n"))
+}
+
+// JSON has no way to write NaN or infinity, so they become null like a missing amount
+fn number(amount: Option<f64>) -> String {
+    match amount {
+        Some(x) if x.is_finite() => format!("{:.2}", x),
+        _ => "null".to_owned(),
+    }
+}
+
+fn string(s: &str) -> String {
+    let mut escaped = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::flapjack_stack::flapjack_stack_builder::FlapJackStackBuilder;
+
+    #[test]
+    fn test_json_directives() {
+        let log = "# comment
+CREATE \"Checking (Bank)\"
+
+INCREMENT \"Checking (Bank)\" 25.50 \"got paid\"";
+
+        let stack = FlapJackStackBuilder::new(log, None).build().unwrap();
+        let expected = "[
+    {\"line\": 2, \"command\": \"CREATE\", \"wallet\": \"Checking (Bank)\", \"amount\": null, \"comment\": null},
+    {\"line\": 4, \"command\": \"INCREMENT\", \"wallet\": \"Checking (Bank)\", \"amount\": 25.50, \"comment\": \"got paid\"}
+]
+";
+
+        assert_eq!(super::directives(&stack), expected);
+        assert_eq!(
+            super::balances(&stack),
+            "[\n    {\"wallet\": \"Checking (Bank)\", \"amount\": 25.50}\n]\n"
+        );
+    }
+}
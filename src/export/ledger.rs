@@ -0,0 +1,193 @@
+use super::dated_directives;
+use crate::flapjack_stack::flapjack::{Directive, FlapJack};
+use crate::flapjack_stack::FlapJackStack;
+use crate::import::DATE_FORMAT;
+
+/// Converts a `FlapJackStack` into a ledger-cli / hledger journal.
+///
+/// CREATE becomes an `account` declaration, INCREMENT and DECREMENT become
+/// two-posting transactions against a counter-account, SET becomes a balance
+/// assignment and DESTROY becomes a transaction that assigns a zero balance.
+#[derive(Debug, Clone)]
+pub struct LedgerExporter {
+    /// Prepended to every wallet name, e.g. `Assets:Checking (Bank)`.
+    pub wallet_prefix: String,
+    /// The other side of an INCREMENT.
+    pub income_account: String,
+    /// The other side of a DECREMENT.
+    pub expense_account: String,
+    /// The other side of a SET or DESTROY.
+    pub adjustment_account: String,
+}
+
+impl Default for LedgerExporter {
+    fn default() -> Self {
+        Self {
+            wallet_prefix: "Assets".to_owned(),
+            income_account: "Equity:Unknown".to_owned(),
+            expense_account: "Equity:Unknown".to_owned(),
+            adjustment_account: "Equity:Unknown".to_owned(),
+        }
+    }
+}
+
+impl LedgerExporter {
+    pub fn export(&self, stack: &FlapJackStack) -> String {
+        let mut journal = String::new();
+
+        let mut counter_accounts = vec![
+            &self.income_account,
+            &self.expense_account,
+            &self.adjustment_account,
+        ];
+        counter_accounts.sort();
+        counter_accounts.dedup();
+
+        for account in counter_accounts {
+            journal.push_str(&format!("account {}\n", account));
+        }
+
+        let mut dated = dated_directives(stack).into_iter();
+
+        for flapjack in &stack.flapjacks {
+            let entry = match flapjack {
+                FlapJack::Blank => continue,
+                FlapJack::Comment(comment) => {
+                    let text = comment.string.trim_start().trim_start_matches('#').trim();
+                    format!("; {}", text)
+                }
+                FlapJack::Directive(_) => {
+                    let dated = dated.next().expect("Every directive has a date");
+                    let date = dated.date.format(DATE_FORMAT);
+
+                    match dated.directive {
+                        Directive::Create { wallet, .. } => {
+                            format!("account {}", self.wallet_account(wallet))
+                        }
+                        Directive::Increment { wallet, amount, .. } => {
+                            let description = dated
+                                .description
+                                .unwrap_or_else(|| format!("Increment {}", wallet));
+
+                            format!(
+                                "{} {}\n    {}  {:.2}\n    {}",
+                                date,
+                                description,
+                                self.wallet_account(wallet),
+                                amount.value(),
+                                self.income_account
+                            )
+                        }
+                        Directive::Decrement { wallet, amount, .. } => {
+                            let description = dated
+                                .description
+                                .unwrap_or_else(|| format!("Decrement {}", wallet));
+
+                            format!(
+                                "{} {}\n    {}  {:.2}\n    {}",
+                                date,
+                                description,
+                                self.wallet_account(wallet),
+                                -amount.value(),
+                                self.expense_account
+                            )
+                        }
+                        Directive::Set { wallet, amount, .. } => {
+                            let description = dated
+                                .description
+                                .unwrap_or_else(|| format!("Set {}", wallet));
+
+                            format!(
+                                "{} {}\n    {}  = {:.2}\n    {}",
+                                date,
+                                description,
+                                self.wallet_account(wallet),
+                                amount.value(),
+                                self.adjustment_account
+                            )
+                        }
+                        Directive::Destroy { wallet, .. } => {
+                            let description = dated
+                                .description
+                                .unwrap_or_else(|| format!("Close {}", wallet));
+
+                            format!(
+                                "{} {}\n    {}  = 0.00\n    {}",
+                                date,
+                                description,
+                                self.wallet_account(wallet),
+                                self.adjustment_account
+                            )
+                        }
+                        // directives ledger has no equivalent for are kept as comments
+                        other @ (Directive::Include { .. }
+                        | Directive::Checkpoint { .. }
+                        | Directive::Chain
+                        | Directive::Recur { .. }
+                        | Directive::Budget { .. }
+                        | Directive::Goal { .. }
+                        | Directive::Policy { .. }
+                        | Directive::Meta { .. }
+                        | Directive::Other { .. }) => {
+                            format!("; {}", other.serialize())
+                        }
+                    }
+                }
+            };
+
+            journal.push('\n');
+            journal.push_str(&entry);
+            journal.push('\n');
+        }
+
+        journal
+    }
+
+    fn wallet_account(&self, wallet: &str) -> String {
+        format!("{}:{}", self.wallet_prefix, wallet)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LedgerExporter;
+    use crate::flapjack_stack::flapjack_stack_builder::FlapJackStackBuilder;
+
+    #[test]
+    fn test_ledger_export() {
+        let log = "# opening
+CREATE \"Checking (Bank)\"
+INCREMENT \"Checking (Bank)\" 50 \"2026-09-01 got paid\"
+DECREMENT \"Checking (Bank)\" 10.5
+SET \"Checking (Bank)\" 200 \"2026-09-03 reconcile\"
+DESTROY \"Checking (Bank)\"";
+
+        let stack = FlapJackStackBuilder::new(log, None).build().unwrap();
+        let journal = LedgerExporter::default().export(&stack);
+
+        let expected = "account Equity:Unknown
+
+; opening
+
+account Assets:Checking (Bank)
+
+2026-09-01 got paid
+    Assets:Checking (Bank)  50.00
+    Equity:Unknown
+
+2026-09-01 Decrement Checking (Bank)
+    Assets:Checking (Bank)  -10.50
+    Equity:Unknown
+
+2026-09-03 reconcile
+    Assets:Checking (Bank)  = 200.00
+    Equity:Unknown
+
+2026-09-03 Close Checking (Bank)
+    Assets:Checking (Bank)  = 0.00
+    Equity:Unknown
+";
+
+        assert_eq!(journal, expected);
+    }
+}
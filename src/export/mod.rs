@@ -0,0 +1,103 @@
+use chrono::{Local, NaiveDate};
+
+use crate::flapjack_stack::flapjack::{Directive, FlapJack};
+use crate::flapjack_stack::FlapJackStack;
+use crate::import::split_date;
+
+pub mod beancount;
+pub mod csv;
+pub mod json;
+pub mod ledger;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+/// A directive flattened into the columns of the JSON and CSV exports.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DirectiveRow {
+    /// The line in the file the directive was read from.
+    pub line: usize,
+    pub command: String,
+    /// None for commands flapjack does not know.
+    pub wallet: Option<String>,
+    pub amount: Option<f64>,
+    pub comment: Option<String>,
+}
+
+/// Returns every directive in the log, leaving out comments and blank lines.
+pub fn directive_rows(stack: &FlapJackStack) -> Vec<DirectiveRow> {
+    stack
+        .flapjacks
+        .iter()
+        .enumerate()
+        .filter_map(|(i, flapjack)| match flapjack {
+            FlapJack::Directive(directive) => Some(DirectiveRow {
+                line: stack.line_number(i),
+                command: directive.keyword(),
+                wallet: directive.wallet().map(|x| x.to_owned()),
+                amount: directive.amount().map(|x| x.value()),
+                comment: directive.comment().map(|x| x.to_owned()),
+            }),
+            FlapJack::Comment(_) | FlapJack::Blank => None,
+        })
+        .collect()
+}
+
+/// A directive together with the date and description an exporter should use for it.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DatedDirective<'a> {
+    pub date: NaiveDate,
+    pub directive: &'a Directive,
+    /// The comment with its leading date removed, if there is anything left.
+    pub description: Option<String>,
+}
+
+/// Plain-text accounting formats need a date on every transaction, but the log only
+/// has dates where a comment starts with one. Each undated directive takes the date of
+/// the closest dated directive before it. Directives before the first dated one take the
+/// first date in the log, or today if the log has no dates at all.
+pub fn dated_directives(stack: &FlapJackStack) -> Vec<DatedDirective<'_>> {
+    let first_date = stack
+        .flapjacks
+        .iter()
+        .filter_map(|flapjack| match flapjack {
+            FlapJack::Directive(directive) => directive_date(directive),
+            FlapJack::Comment(_) | FlapJack::Blank => None,
+        })
+        .next()
+        .unwrap_or_else(|| Local::now().date_naive());
+
+    let mut current_date = first_date;
+    let mut dated = Vec::new();
+
+    for flapjack in &stack.flapjacks {
+        if let FlapJack::Directive(directive) = flapjack {
+            let (date, description) = split_comment(directive);
+            if let Some(date) = date {
+                current_date = date;
+            }
+
+            dated.push(DatedDirective {
+                date: current_date,
+                directive,
+                description,
+            });
+        }
+    }
+
+    dated
+}
+
+fn directive_date(directive: &Directive) -> Option<NaiveDate> {
+    split_comment(directive).0
+}
+
+fn split_comment(directive: &Directive) -> (Option<NaiveDate>, Option<String>) {
+    match directive.comment() {
+        Some(comment) => {
+            let (date, rest) = split_date(comment);
+            let description = Some(rest.to_owned()).filter(|x| !x.is_empty());
+            (date, description)
+        }
+        None => (None, None),
+    }
+}
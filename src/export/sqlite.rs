@@ -0,0 +1,159 @@
+//! A SQLite export of the log for running SQL over it, or pointing other tools at.
+//!
+//! The log stays the source of truth. The database is a snapshot that is not updated when flapjack
+//! writes to the log. It is rebuilt from the stack the next time it is exported, if the hash of the
+//! log has changed since, so it should never be edited by hand.
+
+use rusqlite::{params, Connection, OptionalExtension};
+
+use super::directive_rows;
+use crate::flapjack_stack::checkpoint::archive_hash;
+use crate::flapjack_stack::flapjack::FlapJack;
+use crate::flapjack_stack::{FlapJackDb, FlapJackStack};
+
+const SCHEMA: &str = "
+DROP TABLE IF EXISTS wallets;
+CREATE TABLE wallets (
+    name TEXT PRIMARY KEY,
+    amount REAL NOT NULL
+);
+DROP TABLE IF EXISTS directives;
+CREATE TABLE directives (
+    id INTEGER PRIMARY KEY,
+    file TEXT,
+    line INTEGER NOT NULL,
+    command TEXT NOT NULL,
+    wallet TEXT,
+    amount REAL,
+    comment TEXT,
+    balance REAL
+);
+";
+
+/// The hash the database was last built from is stored under this key in `meta`.
+const HASH_KEY: &str = "log_hash";
+
+/// Hashes the log together with every file it includes.
+pub fn log_hash(stack: &FlapJackStack) -> String {
+    let files = (0..stack.files.len())
+        .map(|file| stack.serialize_file(file))
+        .collect::<Vec<String>>();
+    archive_hash(files.join("\0").as_bytes())
+}
+
+/// Rebuilds the database from the stack unless it was already built from the same log.
+/// Returns whether it was rebuilt.
+///
+/// `wallets` has the current amount of every open wallet. `directives` has every directive in
+/// order, with the file and line it was read from and the wallet's `balance` right after it.
+pub fn sync(stack: &FlapJackStack, connection: &mut Connection) -> rusqlite::Result<bool> {
+    let hash = log_hash(stack);
+    connection.execute_batch(
+        "CREATE TABLE IF NOT EXISTS meta (key TEXT PRIMARY KEY, value TEXT NOT NULL);",
+    )?;
+    let stored_hash: Option<String> = connection
+        .query_row(
+            "SELECT value FROM meta WHERE key = ?1",
+            params![HASH_KEY],
+            |row| row.get(0),
+        )
+        .optional()?;
+    if stored_hash.as_deref() == Some(hash.as_str()) {
+        return Ok(false);
+    }
+
+    let transaction = connection.transaction()?;
+    transaction.execute_batch(SCHEMA)?;
+
+    {
+        let mut insert = transaction.prepare(
+            "INSERT INTO directives (file, line, command, wallet, amount, comment, balance)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        )?;
+
+        // the wallets are replayed to get the balance after each directive
+        let mut db = FlapJackDb::from_flaps(&Vec::new());
+        let indices = stack
+            .flapjacks
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| matches!(x, FlapJack::Directive(_)))
+            .map(|(i, _)| i);
+        for (i, row) in indices.zip(directive_rows(stack)) {
+            db.update(&stack.flapjacks[i]);
+            let balance = row
+                .wallet
+                .as_ref()
+                .and_then(|x| db.wallet_amounts.get(x))
+                .copied();
+            let file = stack.files[stack.origins[i]].path.clone();
+
+            insert.execute(params![
+                file,
+                row.line,
+                row.command,
+                row.wallet,
+                row.amount,
+                row.comment,
+                balance
+            ])?;
+        }
+
+        let mut insert =
+            transaction.prepare("INSERT INTO wallets (name, amount) VALUES (?1, ?2)")?;
+        for (name, amount) in &stack.db.wallet_amounts {
+            insert.execute(params![name, amount])?;
+        }
+    }
+
+    transaction.execute(
+        "INSERT OR REPLACE INTO meta (key, value) VALUES (?1, ?2)",
+        params![HASH_KEY, hash],
+    )?;
+    transaction.commit()?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::sync;
+    use crate::flapjack_stack::flapjack_stack_builder::FlapJackStackBuilder;
+    use rusqlite::Connection;
+
+    #[test]
+    fn test_sync() {
+        let log = "CREATE Cash\nINCREMENT Cash 10 lunch\n# spent\nDECREMENT Cash 2.5\nCREATE Jar";
+        let stack = FlapJackStackBuilder::new(log, None).build().unwrap();
+        let mut connection = Connection::open_in_memory().unwrap();
+
+        assert!(sync(&stack, &mut connection).unwrap());
+        // nothing changed, so nothing is rebuilt
+        assert!(!sync(&stack, &mut connection).unwrap());
+
+        let balances = connection
+            .prepare("SELECT line, command, balance FROM directives ORDER BY id")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<Result<Vec<(usize, String, Option<f64>)>, _>>()
+            .unwrap();
+        assert_eq!(
+            balances,
+            vec![
+                (1, "CREATE".to_owned(), Some(0.0)),
+                (2, "INCREMENT".to_owned(), Some(10.0)),
+                (4, "DECREMENT".to_owned(), Some(7.5)),
+                (5, "CREATE".to_owned(), Some(0.0)),
+            ]
+        );
+
+        let stack = FlapJackStackBuilder::new("CREATE Cash\nSET Cash 3", None)
+            .build()
+            .unwrap();
+        assert!(sync(&stack, &mut connection).unwrap());
+        let total: f64 = connection
+            .query_row("SELECT SUM(amount) FROM wallets", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(total, 3.0);
+    }
+}
@@ -1,6 +1,9 @@
+use std::env;
 use std::fs;
 use std::fs::OpenOptions;
 
+use crate::encryption;
+
 // Clippy does not like "CouldNot" as a prefix for all error variants.
 #[allow(clippy::enum_variant_names)]
 #[non_exhaustive]
@@ -36,7 +39,6 @@ pub fn init_log_db() -> Result<String, InitLogDbError> {
 
     let file = OpenOptions::new()
         .create_new(true)
-        .write(true)
         .append(true)
         .open(file_path);
 
@@ -53,3 +55,54 @@ pub fn init_log_db() -> Result<String, InitLogDbError> {
 
     Ok(path_string)
 }
+
+/// Read by `read_new_passphrase` before it asks for one.
+pub const NEW_PASSPHRASE_VAR: &str = "FLAPJACK_NEW_PASSPHRASE";
+
+#[non_exhaustive]
+#[derive(thiserror::Error, Debug)]
+pub enum PassphraseError {
+    #[error("Could not read the passphrase. ({0})")]
+    CouldNotRead(#[source] std::io::Error),
+    #[error("The passphrases do not match.")]
+    DoNotMatch,
+    #[error("The passphrase is empty.")]
+    Empty,
+}
+
+/// The passphrase for an encrypted log, from `FLAPJACK_PASSPHRASE` or else asked for on the terminal.
+/// Returns `None` when the log is not encrypted.
+pub fn passphrase_for(path: &str, bytes: &[u8]) -> Result<Option<String>, PassphraseError> {
+    if !encryption::is_encrypted(bytes) {
+        return Ok(None);
+    }
+    if let Ok(passphrase) = env::var(encryption::PASSPHRASE_VAR) {
+        return Ok(Some(passphrase));
+    }
+
+    let passphrase = rpassword::prompt_password(format!("Passphrase for {}: ", path))
+        .map_err(PassphraseError::CouldNotRead)?;
+    Ok(Some(passphrase))
+}
+
+/// A new passphrase, from `FLAPJACK_NEW_PASSPHRASE` or else asked for twice on the terminal.
+pub fn read_new_passphrase() -> Result<String, PassphraseError> {
+    let passphrase = match env::var(NEW_PASSPHRASE_VAR) {
+        Ok(x) => x,
+        Err(_) => {
+            let first = rpassword::prompt_password("New passphrase: ")
+                .map_err(PassphraseError::CouldNotRead)?;
+            let second = rpassword::prompt_password("Repeat the new passphrase: ")
+                .map_err(PassphraseError::CouldNotRead)?;
+            if first != second {
+                return Err(PassphraseError::DoNotMatch);
+            }
+            first
+        }
+    };
+
+    if passphrase.is_empty() {
+        return Err(PassphraseError::Empty);
+    }
+    Ok(passphrase)
+}
@@ -0,0 +1,270 @@
+//! Budgets, which limit what can be spent each period.
+//!
+//! A budget named after a wallet counts every DECREMENT of that wallet. Any other budget is a
+//! category, and counts every DECREMENT whose comment has the category as a word.
+
+use std::fmt;
+
+use chrono::{Datelike, Days, NaiveDate};
+
+use super::flapjack::{Directive, FlapJack};
+use super::schedule::with_date;
+use super::FlapJackStack;
+use crate::import::split_date;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Period {
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+impl Period {
+    pub fn parse(text: &str) -> Option<Self> {
+        match text {
+            "weekly" => Some(Self::Weekly),
+            "monthly" => Some(Self::Monthly),
+            "yearly" => Some(Self::Yearly),
+            _ => None,
+        }
+    }
+
+    /// The first day of the period `date` is in. Weeks start on Monday.
+    pub fn start(&self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Self::Weekly => date - Days::new(date.weekday().num_days_from_monday().into()),
+            Self::Monthly => date.with_day(1).expect("Every month has a first day"),
+            Self::Yearly => date.with_ordinal(1).expect("Every year has a first day"),
+        }
+    }
+}
+
+impl fmt::Display for Period {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Weekly => write!(f, "weekly"),
+            Self::Monthly => write!(f, "monthly"),
+            Self::Yearly => write!(f, "yearly"),
+        }
+    }
+}
+
+/// How much of a budget has been spent in the current period.
+#[derive(Debug, PartialEq, Clone)]
+pub struct BudgetStatus {
+    pub name: String,
+    pub period: Period,
+    pub allotted: f64,
+    pub spent: f64,
+}
+
+impl BudgetStatus {
+    pub fn remaining(&self) -> f64 {
+        self.allotted - self.spent
+    }
+
+    /// The share of the budget that has been spent, as a percentage.
+    pub fn percent_spent(&self) -> f64 {
+        if self.allotted == 0.0 {
+            return 0.0;
+        }
+        self.spent / self.allotted * 100.0
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.spent > self.allotted
+    }
+}
+
+impl FlapJackStack {
+    /// Every budget with what was spent in the period that `today` is in, in the order they were
+    /// first set. A later BUDGET with the same name replaces the earlier one.
+    /// Directives count on the date their comment starts with, or else on the date of the closest
+    /// dated directive before them. Directives before the first date are not counted.
+    pub fn budgets(&self, today: NaiveDate) -> Vec<BudgetStatus> {
+        let mut budgets: Vec<BudgetStatus> = Vec::new();
+        for flapjack in &self.flapjacks {
+            if let FlapJack::Directive(Directive::Budget {
+                name,
+                amount,
+                period,
+            }) = flapjack
+            {
+                let status = BudgetStatus {
+                    name: name.clone(),
+                    period: period.unwrap_or(Period::Monthly),
+                    allotted: amount.value(),
+                    spent: 0.0,
+                };
+                match budgets.iter_mut().find(|x| x.name == *name) {
+                    Some(budget) => *budget = status,
+                    None => budgets.push(status),
+                }
+            }
+        }
+
+        let dates = self.counted_dates();
+        for (flapjack, date) in self.flapjacks.iter().zip(dates) {
+            let (wallet, amount, description) = match flapjack {
+                FlapJack::Directive(Directive::Decrement {
+                    wallet,
+                    amount,
+                    comment,
+                }) => (wallet, amount.value(), description(comment)),
+                _ => continue,
+            };
+            let date = match date {
+                Some(x) => x,
+                None => continue,
+            };
+
+            for budget in budgets.iter_mut() {
+                if self.is_counted(budget, wallet, description, date, today) {
+                    budget.spent += amount;
+                }
+            }
+        }
+
+        budgets
+    }
+
+    /// The latest BUDGET of each name, and the DECREMENTs of open wallets that count towards a
+    /// budget in the period `today` is in, with the date they count on at the start of their comment.
+    /// A checkpoint keeps these lines.
+    pub(super) fn budget_lines(&self, today: NaiveDate) -> Vec<(usize, FlapJack)> {
+        let budgets = self.budgets(today);
+        let mut lines = Vec::new();
+
+        for budget in &budgets {
+            let latest = self.flapjacks.iter().rposition(|x| {
+                matches!(x, FlapJack::Directive(Directive::Budget { name, .. }) if *name == budget.name)
+            });
+            lines.extend(latest.map(|i| (i, self.flapjacks[i].clone())));
+        }
+
+        let dates = self.counted_dates();
+        for (i, (flapjack, date)) in self.flapjacks.iter().zip(dates).enumerate() {
+            let (directive, wallet, comment) = match flapjack {
+                FlapJack::Directive(
+                    directive @ Directive::Decrement {
+                        wallet, comment, ..
+                    },
+                ) => (directive, wallet, comment),
+                _ => continue,
+            };
+            let date = match date {
+                Some(x) if self.db.wallet_amounts.contains_key(wallet) => x,
+                _ => continue,
+            };
+            let description = description(comment);
+            if !budgets
+                .iter()
+                .any(|x| self.is_counted(x, wallet, description, date, today))
+            {
+                continue;
+            }
+
+            let has_date = comment
+                .as_deref()
+                .is_some_and(|x| split_date(x).0.is_some());
+            let directive = if has_date {
+                directive.clone()
+            } else {
+                with_date(directive, date)
+            };
+            lines.push((i, FlapJack::Directive(directive)));
+        }
+
+        lines
+    }
+
+    // the date every line counts on: the date its comment starts with, or else the date of the
+    // closest dated directive before it
+    fn counted_dates(&self) -> Vec<Option<NaiveDate>> {
+        let mut date = None;
+        self.flapjacks
+            .iter()
+            .map(|flapjack| {
+                if let FlapJack::Directive(directive) = flapjack {
+                    date = directive.comment().and_then(|x| split_date(x).0).or(date);
+                }
+                date
+            })
+            .collect()
+    }
+
+    fn is_counted(
+        &self,
+        budget: &BudgetStatus,
+        wallet: &str,
+        description: &str,
+        date: NaiveDate,
+        today: NaiveDate,
+    ) -> bool {
+        date <= today
+            && date >= budget.period.start(today)
+            && self.counts_towards(&budget.name, wallet, description)
+    }
+
+    fn counts_towards(&self, budget: &str, wallet: &str, description: &str) -> bool {
+        if self.db.wallet_amounts.contains_key(budget) {
+            return wallet == budget;
+        }
+
+        description
+            .split(|x: char| !x.is_alphanumeric() && x != '-' && x != '_')
+            .any(|word| word.eq_ignore_ascii_case(budget))
+    }
+}
+
+// the comment without the date it starts with
+fn description(comment: &Option<String>) -> &str {
+    match comment {
+        Some(comment) => split_date(comment).1,
+        None => "",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BudgetStatus, Period};
+    use crate::flapjack_stack::flapjack_stack_builder::FlapJackStackBuilder;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_budgets() {
+        let log = r#"CREATE Checking
+CREATE Cash
+BUDGET groceries 400
+BUDGET Cash 50 weekly
+DECREMENT Checking 120 "2026-09-30 Groceries"
+DECREMENT Checking 300 "2026-10-02 groceries at the market"
+DECREMENT Cash 20 "2026-10-12 lunch"
+DECREMENT Checking 90.5 "weekly groceries"
+DECREMENT Cash 40 "2026-10-19 groceries"
+BUDGET Cash 60 weekly"#;
+        let stack = FlapJackStackBuilder::new(log, None).build().unwrap();
+        let today = NaiveDate::from_ymd_opt(2026, 10, 19).unwrap();
+
+        assert_eq!(
+            stack.budgets(today),
+            vec![
+                BudgetStatus {
+                    name: "groceries".to_owned(),
+                    period: Period::Monthly,
+                    allotted: 400.0,
+                    spent: 430.5,
+                },
+                // the week started on Monday the 19th
+                BudgetStatus {
+                    name: "Cash".to_owned(),
+                    period: Period::Weekly,
+                    allotted: 60.0,
+                    spent: 40.0,
+                },
+            ]
+        );
+        assert!(stack.budgets(today)[0].is_over());
+    }
+}
@@ -0,0 +1,282 @@
+//! The hash chain that makes edits to past directives visible.
+//!
+//! After a `CHAIN` directive, every directive ends with a `# chain:<hash>` comment. The hash covers
+//! the line before it (with that line's own hash) and the directive itself, so changing, adding or
+//! removing a directive breaks the chain at the next one. Directives before `CHAIN` are not covered.
+//! Directives are hashed as `serialize` writes them, except that the amount is hashed by its value.
+//! `flapjack fmt` only changes how amounts are written and how lines are laid out, so it does not break
+//! the chain.
+
+use std::fs;
+use std::path::Path;
+
+use sha2::{Digest, Sha256};
+
+use super::checkpoint::archive_hash;
+use super::flapjack::{Directive, FlapJack};
+use super::FlapJackStack;
+
+pub const CHAIN_TAG: &str = "chain:";
+
+// the number of hex digits kept from each hash
+const HASH_LENGTH: usize = 16;
+
+#[derive(thiserror::Error, Debug, PartialEq, Clone)]
+pub enum VerifyError {
+    #[error(
+        "{file}, line {line}: The hash does not match. This line or the one before it was changed."
+    )]
+    HashMismatch { file: String, line: usize },
+    #[error("{file}, line {line}: The directive has no hash, but it comes after CHAIN.")]
+    MissingHash { file: String, line: usize },
+    #[error(
+        "{file}, line {line}: The archive \"{archive}\" does not match the hash of its checkpoint."
+    )]
+    ArchiveMismatch {
+        file: String,
+        line: usize,
+        archive: String,
+    },
+    #[error("{file}, line {line}: Could not read the archive \"{archive}\". ({kind})")]
+    CouldNotReadArchive {
+        file: String,
+        line: usize,
+        archive: String,
+        kind: std::io::ErrorKind,
+    },
+}
+
+/// Hashes a directive together with the line before it.
+pub fn link(previous_line: &str, content: &str) -> String {
+    let digest = Sha256::digest(format!("{}\n{}", previous_line, content).as_bytes());
+    digest
+        .iter()
+        .map(|x| format!("{:02x}", x))
+        .collect::<String>()[..HASH_LENGTH]
+        .to_owned()
+}
+
+/// The text a directive is hashed as, with the amount written by value so `5`, `5.0` and `5.00`
+/// hash the same.
+fn hashed_text(flapjack: &FlapJack) -> String {
+    match flapjack {
+        FlapJack::Directive(x) => x.canonical_text(),
+        _ => flapjack.serialize(),
+    }
+}
+
+/// The words of a trailing comment other than its chain hash, for a line that is hashed again.
+pub(super) fn without_chain_hash(comment: &str) -> Option<String> {
+    let words = comment
+        .trim_start_matches('#')
+        .split_whitespace()
+        .filter(|x| !x.starts_with(CHAIN_TAG))
+        .collect::<Vec<&str>>();
+    if words.is_empty() {
+        return None;
+    }
+    Some(words.join(" "))
+}
+
+impl FlapJackStack {
+    /// Whether new directives are given a hash, which is the case once the log has a `CHAIN` directive.
+    pub fn is_chained(&self) -> bool {
+        self.flapjacks
+            .contains(&FlapJack::Directive(Directive::Chain))
+    }
+
+    /// The hash written after the directive at `index`, if there is one.
+    pub fn chain_hash(&self, index: usize) -> Option<String> {
+        let comment = self.trailing_comment(index)?;
+        let last_word = comment.split_whitespace().last()?;
+        last_word.strip_prefix(CHAIN_TAG).map(|x| x.to_owned())
+    }
+
+    /// The directive at `index` as it is hashed by the directive after it.
+    fn chained_line(&self, index: usize) -> String {
+        let hashed = hashed_text(&self.flapjacks[index]);
+        match self.chain_hash(index) {
+            Some(hash) => format!("{} # {}{}", hashed, CHAIN_TAG, hash),
+            None => hashed,
+        }
+    }
+
+    /// The line a flapjack inserted at `index` should be written as, with `note` in its trailing comment.
+    /// None if it can simply be serialized.
+    pub(super) fn new_source(
+        &self,
+        index: usize,
+        flapjack: &FlapJack,
+        note: Option<&str>,
+    ) -> Option<String> {
+        let serialized = flapjack.serialize();
+        let hashed = hashed_text(flapjack);
+        // the hash always comes last
+        let mut trailing: Vec<String> = note.map(|x| x.to_owned()).into_iter().collect();
+
+        if matches!(flapjack, FlapJack::Directive(_)) && self.is_chained() {
+            let previous_line = self.flapjacks[..index]
+                .iter()
+                .rposition(|x| matches!(x, FlapJack::Directive(_)))
+                .map(|x| self.chained_line(x))
+                .unwrap_or_default();
+
+            let hash = link(&previous_line, &hashed);
+            trailing.push(format!("{}{}", CHAIN_TAG, hash));
+        }
+
+        if trailing.is_empty() {
+            return None;
+        }
+        Some(format!("{} # {}", serialized, trailing.join(" ")))
+    }
+
+    /// Checks the hash of every chained directive and every checkpoint archive,
+    /// and returns the number of directives that were checked.
+    pub fn verify(&self) -> Result<usize, VerifyError> {
+        let mut checked = 0;
+        let mut chain_started = false;
+        let mut previous_line = String::new();
+
+        for (i, flapjack) in self.flapjacks.iter().enumerate() {
+            let directive = match flapjack {
+                FlapJack::Directive(x) => x,
+                _ => continue,
+            };
+
+            let file = self.files[self.origins[i]]
+                .path
+                .clone()
+                .unwrap_or_else(|| "log".to_owned());
+            let line = self.line_number(i);
+
+            match self.chain_hash(i) {
+                Some(hash) => {
+                    if hash != link(&previous_line, &hashed_text(flapjack)) {
+                        return Err(VerifyError::HashMismatch { file, line });
+                    }
+                    checked += 1;
+                }
+                None if chain_started => return Err(VerifyError::MissingHash { file, line }),
+                None => {}
+            }
+
+            if let Directive::Checkpoint { archive, hash } = directive {
+                self.verify_archive(archive, hash, &file, line)?;
+                checked += 1;
+            }
+
+            if *directive == Directive::Chain {
+                chain_started = true;
+            }
+            previous_line = self.chained_line(i);
+        }
+
+        Ok(checked)
+    }
+
+    fn verify_archive(
+        &self,
+        archive: &str,
+        hash: &str,
+        file: &str,
+        line: usize,
+    ) -> Result<(), VerifyError> {
+        let directory = Path::new(file).parent().unwrap_or_else(|| Path::new(""));
+        let archived =
+            fs::read(directory.join(archive)).map_err(|e| VerifyError::CouldNotReadArchive {
+                file: file.to_owned(),
+                line,
+                archive: archive.to_owned(),
+                kind: e.kind(),
+            })?;
+
+        if archive_hash(&archived) != hash {
+            return Err(VerifyError::ArchiveMismatch {
+                file: file.to_owned(),
+                line,
+                archive: archive.to_owned(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::VerifyError;
+    use crate::flapjack_stack::flapjack_stack_builder::FlapJackStackBuilder;
+    use crate::flapjack_stack::format::FormatOptions;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn test_appended_directives_are_chained() {
+        let path = env::temp_dir().join("flapjack_chain.flap");
+        fs::write(&path, "CREATE Cash\nINCREMENT Cash 5\nCHAIN\n").unwrap();
+
+        let mut stack = FlapJackStackBuilder::from_file(&path.to_string_lossy())
+            .build()
+            .unwrap();
+        stack
+            .increment_wallet_amount("Cash", 10.0, Some("lunch"))
+            .unwrap();
+        stack.decrement_wallet_amount("Cash", 2.0, None).unwrap();
+        assert_eq!(stack.verify(), Ok(2));
+
+        // a stack read back from the file verifies the same way
+        let raw = fs::read_to_string(&path).unwrap();
+        assert!(raw.contains("INCREMENT Cash 10 lunch # chain:"));
+        let stack = FlapJackStackBuilder::new(&raw, None).build().unwrap();
+        assert_eq!(stack.verify(), Ok(2));
+
+        // changing the amount breaks the chain on that line
+        let edited = raw.replace("INCREMENT Cash 10 lunch", "INCREMENT Cash 100 lunch");
+        let stack = FlapJackStackBuilder::new(&edited, None).build().unwrap();
+        assert_eq!(
+            stack.verify(),
+            Err(VerifyError::HashMismatch {
+                file: "log".to_owned(),
+                line: 4
+            })
+        );
+
+        // directives before CHAIN are not covered
+        let edited = raw.replace("INCREMENT Cash 5", "INCREMENT Cash 6");
+        let stack = FlapJackStackBuilder::new(&edited, None).build().unwrap();
+        assert_eq!(stack.verify(), Ok(2));
+    }
+
+    #[test]
+    fn test_unchained_directive_after_chain() {
+        let log = "CREATE Cash\nCHAIN\nINCREMENT Cash 5";
+        let stack = FlapJackStackBuilder::new(log, None).build().unwrap();
+
+        assert_eq!(
+            stack.verify(),
+            Err(VerifyError::MissingHash {
+                file: "log".to_owned(),
+                line: 3
+            })
+        );
+    }
+
+    #[test]
+    fn test_formatted_log_still_verifies() {
+        let mut stack = FlapJackStackBuilder::new("CREATE Cash\nCHAIN\n", None)
+            .build()
+            .unwrap();
+        stack.increment_wallet_amount("Cash", 5.0, None).unwrap();
+        stack
+            .decrement_wallet_amount("Cash", 0.5, Some("tip"))
+            .unwrap();
+
+        stack.format(FormatOptions::default());
+        let formatted = stack.serialize();
+        assert!(formatted.contains("INCREMENT Cash 5.00"));
+
+        let stack = FlapJackStackBuilder::new(&formatted, None).build().unwrap();
+        assert_eq!(stack.verify(), Ok(2));
+    }
+}
@@ -0,0 +1,291 @@
+//! Checkpoints written by `flapjack compact`.
+
+use chrono::Local;
+use sha2::{Digest, Sha256};
+
+use super::chain::without_chain_hash;
+use super::flapjack::{Amount, Comment, Directive, FlapJack};
+use super::FlapJackStack;
+use crate::import::DATE_FORMAT;
+
+/// Hashes an archived log the way it is written in a `CHECKPOINT` directive.
+pub fn archive_hash(archived: &[u8]) -> String {
+    let digest = Sha256::digest(archived);
+    let hex = digest
+        .iter()
+        .map(|x| format!("{:02x}", x))
+        .collect::<String>();
+    format!("sha256:{}", hex)
+}
+
+impl FlapJackStack {
+    /// Returns a new log that starts where this one ends: a header comment, a `CHECKPOINT`
+    /// pointing at the archived history, and a CREATE and SET for every wallet that is still open.
+    /// The RECUR templates are kept in between, along with the latest occurrence of each so their
+    /// schedules carry on where they left off, and so are the latest BUDGET of each name and the
+    /// DECREMENTs that count towards a budget in the current period. The latest GOAL of each wallet is
+    /// kept with a dated SET of the balance the wallet's projection starts from.
+    /// A chained log stays chained, with the CHECKPOINT and every line after it hashed again.
+    pub fn checkpoint(&self, archive: &str, hash: &str) -> FlapJackStack {
+        let date = Local::now().date_naive();
+        let today = date.format(DATE_FORMAT).to_string();
+
+        let mut header = vec![FlapJack::Comment(Comment::new(format!(
+            "# Checkpoint made on {}. The history before it is in {}.",
+            today, archive
+        )))];
+        if self.is_chained() {
+            header.push(FlapJack::Directive(Directive::Chain));
+        }
+
+        let mut stack = FlapJackStack::new(header, self.log_path.clone());
+        // an encrypted log stays encrypted
+        stack.files[0].key = self.files[0].key.clone();
+
+        stack.carry(
+            FlapJack::Directive(Directive::Checkpoint {
+                archive: archive.to_owned(),
+                hash: hash.to_owned(),
+            }),
+            None,
+        );
+        stack.carry(FlapJack::Blank, None);
+
+        for wallet in self.return_wallet_names() {
+            stack.carry(
+                FlapJack::Directive(Directive::Create {
+                    wallet: wallet.clone(),
+                    comment: None,
+                    policy: self.db.policies.get(&wallet).cloned(),
+                    meta: self
+                        .db
+                        .metadata
+                        .get(&wallet)
+                        .map(|x| x.clone().into_iter().collect())
+                        .unwrap_or_default(),
+                }),
+                None,
+            );
+        }
+
+        for goal in self.goals(date) {
+            if let Some((start, balance)) = self.first_dated_balance(&goal.wallet) {
+                stack.carry(
+                    FlapJack::Directive(Directive::Set {
+                        wallet: goal.wallet,
+                        amount: checkpoint_amount(balance),
+                        comment: Some(format!("{} checkpoint", start.format(DATE_FORMAT))),
+                    }),
+                    None,
+                );
+            }
+        }
+
+        let mut kept = self
+            .schedule_lines()
+            .into_iter()
+            .chain(self.goal_lines())
+            .map(|x| (x, self.flapjacks[x].clone()))
+            .collect::<Vec<_>>();
+        kept.extend(self.budget_lines(date));
+        // in the order of the log, and only once if a line is kept for more than one reason
+        kept.sort_by_key(|(x, _)| *x);
+        kept.dedup_by_key(|(x, _)| *x);
+        for (index, flapjack) in kept {
+            let note = self
+                .trailing_comment(index)
+                .and_then(|x| without_chain_hash(&x));
+            stack.carry(flapjack, note.as_deref());
+        }
+
+        for wallet in self.return_wallet_names() {
+            stack.carry(
+                FlapJack::Directive(Directive::Set {
+                    amount: checkpoint_amount(self.amount(&wallet)),
+                    wallet,
+                    comment: Some(format!("{} checkpoint", today)),
+                }),
+                None,
+            );
+        }
+
+        // end the log with a new line
+        stack.carry(FlapJack::Blank, None);
+        stack
+    }
+
+    // adds a line to the end of a checkpoint that is not written yet, hashed if the checkpoint is chained
+    fn carry(&mut self, flapjack: FlapJack, note: Option<&str>) {
+        let source = self.new_source(self.flapjacks.len(), &flapjack, note);
+        self.db.update(&flapjack);
+        self.flapjacks.push(flapjack);
+        self.sources.push(source);
+        self.origins.push(0);
+    }
+}
+
+/// Balances are sums of floats, so `0.1 + 0.2` is written as `0.30` rather than `0.30000000000000004`.
+/// Up to eight decimal places are kept for wallets that track fractions of a cent.
+fn checkpoint_amount(balance: f64) -> Amount {
+    let text = format!("{:.8}", balance);
+    let (whole, decimals) = text.split_once('.').unwrap_or((&text, ""));
+    let decimals = decimals.trim_end_matches('0');
+    let text = format!("{}.{:0<2}", whole, decimals);
+
+    Amount::parse(&text).unwrap_or_else(|| Amount::new(balance))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{archive_hash, checkpoint_amount};
+    use crate::flapjack_stack::chain::VerifyError;
+    use crate::flapjack_stack::flapjack_stack_builder::FlapJackStackBuilder;
+    use chrono::{Datelike, Local, Months, NaiveDate};
+
+    fn date(text: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(text, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_archive_hash() {
+        assert_eq!(
+            archive_hash(b"abc"),
+            "sha256:ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_checkpoint_amount() {
+        assert_eq!(checkpoint_amount(0.1 + 0.2).as_str(), "0.30");
+        assert_eq!(checkpoint_amount(-4.0).as_str(), "-4.00");
+        assert_eq!(checkpoint_amount(0.125).as_str(), "0.125");
+    }
+
+    #[test]
+    fn test_checkpoint() {
+        let log = "CREATE Cash\nCREATE Old\nINCREMENT Cash 0.1\nINCREMENT Cash 0.2\nDESTROY Old";
+        let stack = FlapJackStackBuilder::new(log, None).build().unwrap();
+
+        let checkpoint = stack.checkpoint("old.flap", "sha256:00");
+        let serialized = checkpoint.serialize();
+        let lines = serialized.lines().collect::<Vec<&str>>();
+
+        assert!(lines[0].starts_with("# Checkpoint made on "));
+        assert_eq!(lines[1], "CHECKPOINT old.flap sha256:00");
+        assert_eq!(lines[3], "CREATE Cash");
+        assert!(lines[4].starts_with("SET Cash 0.30 \""));
+        assert_eq!(lines.len(), 5);
+        assert!(serialized.ends_with('\n'));
+        assert_eq!(checkpoint.db.wallet_amounts.get("Cash"), Some(&0.3));
+        assert!(!checkpoint.db.wallet_amounts.contains_key("Old"));
+    }
+
+    #[test]
+    fn test_checkpoint_keeps_policy_and_details() {
+        let log = "CREATE Card policy=warn limit=500 bank=Chase\nMETA Card last4=1234";
+        let stack = FlapJackStackBuilder::new(log, None).build().unwrap();
+
+        let checkpoint = stack.checkpoint("old.flap", "sha256:00");
+        let serialized = checkpoint.serialize();
+
+        assert_eq!(
+            serialized.lines().nth(3),
+            Some("CREATE Card policy=warn limit=500 bank=Chase last4=1234")
+        );
+    }
+
+    #[test]
+    fn test_checkpoint_of_chained_log_is_chained() {
+        let mut stack = FlapJackStackBuilder::new("CREATE Cash\nCHAIN\n", None)
+            .build()
+            .unwrap();
+        stack.increment_wallet_amount("Cash", 5.0, None).unwrap();
+
+        let checkpoint = stack.checkpoint("old.flap", "sha256:00");
+        let serialized = checkpoint.serialize();
+        assert_eq!(serialized.lines().nth(1), Some("CHAIN"));
+
+        // the archive is not there, so verifying stops at the CHECKPOINT after its hash was checked
+        let read_back = FlapJackStackBuilder::new(&serialized, None)
+            .build()
+            .unwrap();
+        assert!(matches!(
+            read_back.verify(),
+            Err(VerifyError::CouldNotReadArchive { line: 3, .. })
+        ));
+
+        // without the CHECKPOINT every line after CHAIN is still linked to the one before it
+        let without_checkpoint = serialized
+            .lines()
+            .filter(|x| !x.starts_with("CHECKPOINT"))
+            .collect::<Vec<&str>>()
+            .join("\n");
+        let read_back = FlapJackStackBuilder::new(&without_checkpoint, None)
+            .build()
+            .unwrap();
+        assert!(matches!(
+            read_back.verify(),
+            Err(VerifyError::HashMismatch { line: 4, .. })
+        ));
+    }
+
+    #[test]
+    fn test_checkpoint_keeps_schedules() {
+        let log = "CREATE Checking\nRECUR monthly 1 DECREMENT Checking 1200 rent\n";
+        let mut stack = FlapJackStackBuilder::new(log, None).build().unwrap();
+        stack.run_schedule(date("2026-09-20")).unwrap();
+        stack.run_schedule(date("2026-10-19")).unwrap();
+
+        let checkpoint = stack.checkpoint("old.flap", "sha256:00");
+        let serialized = checkpoint.serialize();
+        assert!(serialized.contains("\nRECUR monthly 1 DECREMENT Checking 1200 rent\n"));
+        assert!(!serialized.contains("2026-09-01 rent"));
+        assert!(serialized.contains("DECREMENT Checking 1200 \"2026-10-01 rent\" # recur:"));
+
+        // the schedule carries on from the latest occurrence
+        let mut read_back = FlapJackStackBuilder::new(&serialized, None)
+            .build()
+            .unwrap();
+        assert!(read_back.due_occurrences(date("2026-10-19")).is_empty());
+        assert_eq!(
+            read_back
+                .run_schedule(date("2026-11-02"))
+                .unwrap()
+                .added
+                .len(),
+            1
+        );
+        assert_eq!(read_back.db.wallet_amounts.get("Checking"), Some(&-3600.0));
+    }
+
+    #[test]
+    fn test_checkpoint_keeps_budgets() {
+        let today = Local::now().date_naive();
+        let this_month = today.with_day(1).unwrap();
+        let last_month = this_month - Months::new(1);
+        let log = format!(
+            "CREATE Checking\nBUDGET groceries 300\nDECREMENT Checking 50 \"{} groceries\"\n\
+             BUDGET groceries 400\nDECREMENT Checking 20 \"{} rent\"\n\
+             DECREMENT Checking 30 \"{} groceries\"\nDECREMENT Checking 10 \"more groceries\"\n",
+            last_month, this_month, this_month
+        );
+        let stack = FlapJackStackBuilder::new(&log, None).build().unwrap();
+
+        let checkpoint = stack.checkpoint("old.flap", "sha256:00");
+        let serialized = checkpoint.serialize();
+        assert!(!serialized.contains("BUDGET groceries 300"));
+        assert!(!serialized.contains("rent"));
+        // an undated DECREMENT keeps the date it counts on
+        assert!(serialized.contains(&format!(
+            "DECREMENT Checking 10 \"{} more groceries\"",
+            this_month
+        )));
+
+        let read_back = FlapJackStackBuilder::new(&serialized, None)
+            .build()
+            .unwrap();
+        assert_eq!(read_back.budgets(today), stack.budgets(today));
+        assert_eq!(read_back.budgets(today)[0].spent, 40.0);
+        assert_eq!(read_back.db.wallet_amounts.get("Checking"), Some(&-110.0));
+    }
+}
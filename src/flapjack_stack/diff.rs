@@ -0,0 +1,201 @@
+//! How the balances changed between two logs, or between two points in one log.
+
+use std::collections::{BTreeSet, HashMap};
+
+use super::flapjack::FlapJack;
+use super::{FlapJackDb, FlapJackStack};
+
+/// A wallet whose balance changed, or that has directives on only one side.
+#[derive(Debug, PartialEq, Clone)]
+pub struct WalletDiff {
+    pub wallet: String,
+    /// None if the wallet did not exist.
+    pub before: Option<f64>,
+    /// None if the wallet no longer exists.
+    pub after: Option<f64>,
+    /// The directives on the wallet that account for the change.
+    pub directives: Vec<DiffDirective>,
+}
+
+/// A directive that only one side of a diff has.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DiffDirective {
+    /// Whether the directive is only on the newer side. Otherwise it is only on the older one.
+    pub added: bool,
+    pub file: String,
+    /// The line in the file the directive was read from.
+    pub line: usize,
+    pub text: String,
+}
+
+impl FlapJackStack {
+    /// Compares the first `end` flapjacks of this stack with the first `other_end` flapjacks of `other`.
+    /// Directives are matched by their text with the amount compared by value, so moving or
+    /// reformatting one does not show up, but changing its amount shows it as removed and added again.
+    /// To compare two points in the same log, pass the same stack twice with two `cutoff`s.
+    pub fn diff(&self, end: usize, other: &FlapJackStack, other_end: usize) -> Vec<WalletDiff> {
+        let before = FlapJackDb::from_flaps(&self.flapjacks[..end].to_vec());
+        let after = FlapJackDb::from_flaps(&other.flapjacks[..other_end].to_vec());
+
+        // a directive on both sides cancels out, however many times it appears
+        let mut counts: HashMap<String, isize> = HashMap::new();
+        for text in directive_texts(self, end) {
+            *counts.entry(text).or_default() -= 1;
+        }
+        for text in directive_texts(other, other_end) {
+            *counts.entry(text).or_default() += 1;
+        }
+
+        let mut directives: Vec<(String, DiffDirective)> = Vec::new();
+        for (stack, stack_end, added) in [(self, end, false), (other, other_end, true)] {
+            // of several identical directives, the last ones are the unmatched ones
+            let mut unmatched_directives = Vec::new();
+            for (i, flapjack) in stack.flapjacks[..stack_end].iter().enumerate().rev() {
+                let directive = match flapjack {
+                    FlapJack::Directive(x) => x,
+                    _ => continue,
+                };
+                let wallet = match directive.wallet() {
+                    Some(x) => x,
+                    None => continue,
+                };
+
+                let count = counts
+                    .get_mut(&directive.canonical_text())
+                    .expect("Every directive was counted");
+                let unmatched = if added { *count > 0 } else { *count < 0 };
+                if !unmatched {
+                    continue;
+                }
+                *count += if added { -1 } else { 1 };
+
+                unmatched_directives.push((
+                    wallet.to_owned(),
+                    DiffDirective {
+                        added,
+                        file: stack.files[stack.origins[i]]
+                            .path
+                            .clone()
+                            .unwrap_or_else(|| "log".to_owned()),
+                        line: stack.line_number(i),
+                        text: directive.serialize(),
+                    },
+                ));
+            }
+            directives.extend(unmatched_directives.into_iter().rev());
+        }
+
+        let wallets: BTreeSet<&String> = before
+            .wallet_amounts
+            .keys()
+            .chain(after.wallet_amounts.keys())
+            .chain(directives.iter().map(|(x, _)| x))
+            .collect();
+
+        wallets
+            .into_iter()
+            .map(|wallet| WalletDiff {
+                wallet: wallet.clone(),
+                before: before.wallet_amounts.get(wallet).copied(),
+                after: after.wallet_amounts.get(wallet).copied(),
+                directives: directives
+                    .iter()
+                    .filter(|(x, _)| x == wallet)
+                    .map(|(_, x)| x.clone())
+                    .collect(),
+            })
+            .filter(|x| x.before != x.after || !x.directives.is_empty())
+            .collect()
+    }
+}
+
+fn directive_texts(stack: &FlapJackStack, end: usize) -> Vec<String> {
+    stack.flapjacks[..end]
+        .iter()
+        .filter_map(|flapjack| match flapjack {
+            FlapJack::Directive(x) if x.wallet().is_some() => Some(x.canonical_text()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DiffDirective, WalletDiff};
+    use crate::flapjack_stack::flapjack_stack_builder::FlapJackStackBuilder;
+    use crate::flapjack_stack::history::Position;
+
+    #[test]
+    fn test_diff_two_logs() {
+        let ours = "CREATE Cash\nCREATE Old\nINCREMENT Cash 10\nINCREMENT Old 3";
+        let theirs =
+            "CREATE Cash\nCREATE Old\nINCREMENT Cash 12\nINCREMENT Old 3\nDESTROY Old\nCREATE Jar";
+        let ours = FlapJackStackBuilder::new(ours, None).build().unwrap();
+        let theirs = FlapJackStackBuilder::new(theirs, None).build().unwrap();
+
+        let diff = ours.diff(ours.flapjacks.len(), &theirs, theirs.flapjacks.len());
+        let directive = |added, line, text: &str| DiffDirective {
+            added,
+            file: "log".to_owned(),
+            line,
+            text: text.to_owned(),
+        };
+
+        assert_eq!(
+            diff,
+            vec![
+                WalletDiff {
+                    wallet: "Cash".to_owned(),
+                    before: Some(10.0),
+                    after: Some(12.0),
+                    directives: vec![
+                        directive(false, 3, "INCREMENT Cash 10"),
+                        directive(true, 3, "INCREMENT Cash 12"),
+                    ],
+                },
+                WalletDiff {
+                    wallet: "Jar".to_owned(),
+                    before: None,
+                    after: Some(0.0),
+                    directives: vec![directive(true, 6, "CREATE Jar")],
+                },
+                WalletDiff {
+                    wallet: "Old".to_owned(),
+                    before: Some(3.0),
+                    after: None,
+                    directives: vec![directive(true, 5, "DESTROY Old")],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_two_lines() {
+        let log = "CREATE Cash\nINCREMENT Cash 1\nINCREMENT Cash 1\nDECREMENT Cash 5";
+        let stack = FlapJackStackBuilder::new(log, None).build().unwrap();
+
+        let diff = stack.diff(
+            stack.cutoff(Position::Line(2)),
+            &stack,
+            stack.cutoff(Position::Line(3)),
+        );
+        assert_eq!(diff.len(), 1);
+        assert_eq!((diff[0].before, diff[0].after), (Some(1.0), Some(2.0)));
+        // the second of the two identical increments is the one that was added
+        assert_eq!(diff[0].directives[0].line, 3);
+    }
+
+    #[test]
+    fn test_reformatted_amount_is_not_a_change() {
+        let ours = FlapJackStackBuilder::new("CREATE Cash\nINCREMENT Cash 5", None)
+            .build()
+            .unwrap();
+        let theirs = FlapJackStackBuilder::new("CREATE Cash\nINCREMENT   Cash 5.00", None)
+            .build()
+            .unwrap();
+
+        assert!(ours
+            .diff(ours.flapjacks.len(), &theirs, theirs.flapjacks.len())
+            .is_empty());
+    }
+}
@@ -1,12 +1,49 @@
 use std::fmt;
 
-#[derive(Debug, PartialEq, Clone)]
+use super::budget::Period;
+use super::lexer::{self, LexError, Tokens};
+use super::policy::{Policy, WalletPolicy};
+use super::schedule::{Frequency, Schedule};
+use crate::encryption::EncryptionError;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Command {
     Create,
     Increment,
     Set,
     Destroy,
     Decrement,
+    Include,
+    Checkpoint,
+    Chain,
+    Recur,
+    Budget,
+    Goal,
+    Policy,
+    Meta,
+}
+
+impl Command {
+    /// Returns None if the keyword is not a built in command.
+    pub fn from_keyword(keyword: &str) -> Option<Self> {
+        match keyword {
+            "CREATE" => Some(Self::Create),
+            "INCREMENT" => Some(Self::Increment),
+            "SET" => Some(Self::Set),
+            "DESTROY" => Some(Self::Destroy),
+            "DECREMENT" => Some(Self::Decrement),
+            "INCLUDE" => Some(Self::Include),
+            "CHECKPOINT" => Some(Self::Checkpoint),
+            "CHAIN" => Some(Self::Chain),
+            "RECUR" => Some(Self::Recur),
+            "BUDGET" => Some(Self::Budget),
+            "GOAL" => Some(Self::Goal),
+            "POLICY" => Some(Self::Policy),
+            "META" => Some(Self::Meta),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Display for Command {
@@ -27,15 +64,105 @@ impl fmt::Display for Command {
             Self::Decrement => {
                 write!(f, "DECREMENT")
             }
+            Self::Include => {
+                write!(f, "INCLUDE")
+            }
+            Self::Checkpoint => {
+                write!(f, "CHECKPOINT")
+            }
+            Self::Chain => {
+                write!(f, "CHAIN")
+            }
+            Self::Recur => {
+                write!(f, "RECUR")
+            }
+            Self::Budget => {
+                write!(f, "BUDGET")
+            }
+            Self::Goal => {
+                write!(f, "GOAL")
+            }
+            Self::Policy => {
+                write!(f, "POLICY")
+            }
+            Self::Meta => {
+                write!(f, "META")
+            }
         }
     }
 }
 
+#[derive(thiserror::Error, Debug, PartialEq, Clone)]
+pub enum ParseError {
+    #[error("Line {line}: {command} needs a wallet name.")]
+    MissingWallet { line: usize, command: Command },
+    #[error("Line {line}: {command} needs an amount.")]
+    MissingAmount { line: usize, command: Command },
+    #[error("Line {line}: \"{amount}\" is not a valid amount.")]
+    InvalidAmount { line: usize, amount: String },
+    #[error("Line {line}: {command} has an unexpected parameter \"{param}\".")]
+    UnexpectedParam {
+        line: usize,
+        command: Command,
+        param: String,
+    },
+    #[error("Line {line}: {source}")]
+    Lex { line: usize, source: LexError },
+    #[error("Line {line}: {command} needs a file path.")]
+    MissingPath { line: usize, command: Command },
+    #[error("Line {line}: CHECKPOINT needs the hash of the archived log.")]
+    MissingHash { line: usize },
+    #[error("Line {line}: Could not read included file \"{path}\". ({kind})")]
+    CouldNotReadInclude {
+        line: usize,
+        path: String,
+        kind: std::io::ErrorKind,
+    },
+    #[error("Line {line}: Could not decrypt included file \"{path}\". ({source})")]
+    CouldNotDecryptInclude {
+        line: usize,
+        path: String,
+        source: EncryptionError,
+    },
+    #[error("Line {line}: Including \"{path}\" would include it inside itself.")]
+    IncludeCycle { line: usize, path: String },
+    #[error("Line {line}: \"{path}\" is already included.")]
+    DuplicateInclude { line: usize, path: String },
+    #[error("Line {line}: Only one included file can be the current file.")]
+    MultipleCurrentFiles { line: usize },
+    #[error("Line {line}: RECUR needs a schedule like \"monthly 1\" and the directive to repeat.")]
+    MissingSchedule { line: usize },
+    #[error("Line {line}: \"{schedule}\" is not a valid schedule. Use \"monthly <1-31>\" or \"weekly <1-7>\".")]
+    InvalidSchedule { line: usize, schedule: String },
+    #[error("Line {line}: Only INCREMENT, DECREMENT and SET can be repeated, not {command}.")]
+    InvalidRecurCommand { line: usize, command: String },
+    #[error("Line {line}: RECUR needs a name after \"id=\".")]
+    MissingRecurId { line: usize },
+    #[error("Line {line}: \"{period}\" is not a budget period. Use weekly, monthly or yearly.")]
+    InvalidPeriod { line: usize, period: String },
+    #[error("Line {line}: POLICY needs allow, warn or forbid.")]
+    MissingPolicy { line: usize },
+    #[error("Line {line}: \"{policy}\" is not an overdraft policy. Use allow, warn or forbid.")]
+    InvalidPolicy { line: usize, policy: String },
+    #[error("Line {line}: META needs at least one key=value.")]
+    MissingMeta { line: usize },
+    #[error("Line {line}: The log has a merge conflict that needs to be resolved.")]
+    ConflictMarker { line: usize },
+    /// An error in a file that was included by the log.
+    #[error("{file}: {source}")]
+    InFile {
+        file: String,
+        source: Box<ParseError>,
+    },
+}
+
 #[derive(Debug, PartialEq, Clone)]
-/// Contains either a Directive or a Comment
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// Contains either a Directive, a Comment or a blank line
 pub enum FlapJack {
     Directive(Directive),
     Comment(Comment),
+    Blank,
 }
 
 impl FlapJack {
@@ -43,43 +170,721 @@ impl FlapJack {
         match self {
             Self::Directive(directive) => directive.serialize(),
             Self::Comment(comment) => comment.serialize(),
+            Self::Blank => String::new(),
+        }
+    }
+}
+
+/// An amount of money as it was written in the log.
+/// The original text is kept so that `25.50` is not rewritten as `25.5`.
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Amount {
+    value: f64,
+    text: String,
+}
+
+impl Amount {
+    pub fn new(value: f64) -> Self {
+        Self {
+            value,
+            text: value.to_string(),
         }
     }
+
+    /// Returns None if the text is not a finite number.
+    pub fn parse(text: &str) -> Option<Self> {
+        let value = text.parse::<f64>().ok().filter(|x| x.is_finite())?;
+        Some(Self {
+            value,
+            text: text.to_owned(),
+        })
+    }
+
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
 }
 
-/// A command and params for use in `FlapSequence`s.
-/// Follows the pattern Directive { command: "CREATE", params: ["account", "Checking-Bank"] }.
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+/// The lines that start and split a merge conflict.
+pub const CONFLICT_MARKERS: [&str; 4] = ["<<<<<<<", "|||||||", "=======", ">>>>>>>"];
+
+/// A single line of the log that tells the database to do something.
 /// Directive structure in the log will look like:
 /// INCREMENT checking-bank 46.70 "got paid"
+/// Commands that flapjack does not know are kept as `Other` so that they survive a rewrite of the log.
 #[derive(Debug, PartialEq, Clone)]
-pub struct Directive {
-    pub command: Command,
-    pub params: Vec<String>,
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Directive {
+    /// A new wallet, written as `CREATE Cash [comment] [policy=forbid] [limit=500] [key=value]...`.
+    Create {
+        wallet: String,
+        comment: Option<String>,
+        policy: Option<WalletPolicy>,
+        /// Details about the wallet, like `institution=Chase`, in the order they are written.
+        meta: Vec<(String, String)>,
+    },
+    Increment {
+        wallet: String,
+        amount: Amount,
+        comment: Option<String>,
+    },
+    Decrement {
+        wallet: String,
+        amount: Amount,
+        comment: Option<String>,
+    },
+    Set {
+        wallet: String,
+        amount: Amount,
+        comment: Option<String>,
+    },
+    Destroy {
+        wallet: String,
+        comment: Option<String>,
+    },
+    /// Reads another log in place of this line. New directives are written to the included
+    /// file marked `current`, or to the log itself if there is none.
+    Include { path: String, current: bool },
+    /// Marks the start of a log made by `flapjack compact`. The history before it was moved to
+    /// `archive`, and `hash` is the hash of that file, written as `sha256:<hex>`.
+    Checkpoint { archive: String, hash: String },
+    /// Every directive after this one carries a hash of the line before it and its own content.
+    Chain,
+    /// A template for a directive that repeats on a schedule. `flapjack run-schedule` adds every
+    /// occurrence that is due as a real directive.
+    Recur {
+        schedule: Schedule,
+        /// Names the template, so its occurrences stay linked to it when it is edited.
+        id: Option<String>,
+        template: Box<Directive>,
+    },
+    /// An amount to save up in a wallet.
+    Goal {
+        wallet: String,
+        amount: Amount,
+        comment: Option<String>,
+    },
+    /// Changes what happens when a directive would take a wallet below zero, or below its credit limit.
+    Policy {
+        wallet: String,
+        policy: WalletPolicy,
+    },
+    /// Changes the details of a wallet. A key with an empty value is removed.
+    Meta {
+        wallet: String,
+        meta: Vec<(String, String)>,
+    },
+    /// A limit on what can be spent each period, from a wallet or on a category.
+    /// Budgets without a period are monthly.
+    Budget {
+        name: String,
+        amount: Amount,
+        period: Option<Period>,
+    },
+    Other {
+        command: String,
+        params: Vec<String>,
+    },
 }
 
 impl Directive {
-    pub fn serialize(&self) -> String {
-        let mut combined = String::new();
-        combined.push_str(&self.command.to_string());
-        for param in &self.params {
-            combined.push(' ');
+    /// Builds a directive from a tokenized line, keyword first. Unlike `parse`, this knows which
+    /// params were quoted: a quoted param right after the wallet of a CREATE is always its comment.
+    pub fn from_tokens(tokens: Tokens, line: usize) -> Result<Self, ParseError> {
+        let mut params = tokens.params.into_iter();
+        let keyword = params.next().unwrap_or_default();
+
+        if Command::from_keyword(&keyword) == Some(Command::Create)
+            && tokens.quoted.get(2) == Some(&true)
+        {
+            return Self::parse_create(params, true, line);
+        }
+        Self::parse(&keyword, params.collect(), line)
+    }
+
+    /// Builds a directive from the keyword and params of a line.
+    /// `line` is only used for error messages.
+    pub fn parse(keyword: &str, params: Vec<String>, line: usize) -> Result<Self, ParseError> {
+        // left by `flapjack merge` or git, and must not be read as directives
+        if CONFLICT_MARKERS.iter().any(|x| keyword.starts_with(x)) {
+            return Err(ParseError::ConflictMarker { line });
+        }
+
+        let command = match Command::from_keyword(keyword) {
+            Some(x) => x,
+            None => {
+                return Ok(Self::Other {
+                    command: keyword.to_owned(),
+                    params,
+                })
+            }
+        };
+
+        let mut params = params.into_iter();
+
+        match command {
+            Command::Include => return Self::parse_include(params, line),
+            Command::Checkpoint => return Self::parse_checkpoint(params, line),
+            Command::Chain => {
+                Self::expect_no_more_params(params, command, line)?;
+                return Ok(Self::Chain);
+            }
+            Command::Recur => return Self::parse_recur(params, line),
+            Command::Budget => return Self::parse_budget(params, line),
+            Command::Create => return Self::parse_create(params, false, line),
+            Command::Policy => return Self::parse_policy(params, line),
+            Command::Meta => return Self::parse_meta(params, line),
+            _ => {}
+        }
+
+        let wallet = match params.next() {
+            Some(x) => x,
+            None => return Err(ParseError::MissingWallet { line, command }),
+        };
+
+        // fields are evaluated in the order they are written, so the amount is read before the comment
+        let directive = match command {
+            Command::Destroy => Self::Destroy {
+                wallet,
+                comment: params.next(),
+            },
+            Command::Increment => Self::Increment {
+                wallet,
+                amount: Self::parse_amount(params.next(), command, line)?,
+                comment: params.next(),
+            },
+            Command::Decrement => Self::Decrement {
+                wallet,
+                amount: Self::parse_amount(params.next(), command, line)?,
+                comment: params.next(),
+            },
+            Command::Set => Self::Set {
+                wallet,
+                amount: Self::parse_amount(params.next(), command, line)?,
+                comment: params.next(),
+            },
+            Command::Goal => Self::Goal {
+                wallet,
+                amount: Self::parse_amount(params.next(), command, line)?,
+                comment: params.next(),
+            },
+            Command::Create
+            | Command::Include
+            | Command::Checkpoint
+            | Command::Chain
+            | Command::Recur
+            | Command::Budget
+            | Command::Policy
+            | Command::Meta => {
+                unreachable!("{} is parsed before its params are read", command)
+            }
+        };
+
+        Self::expect_no_more_params(params, command, line)?;
+        Ok(directive)
+    }
+
+    fn expect_no_more_params(
+        mut params: impl Iterator<Item = String>,
+        command: Command,
+        line: usize,
+    ) -> Result<(), ParseError> {
+        match params.next() {
+            Some(param) => Err(ParseError::UnexpectedParam {
+                line,
+                command,
+                param,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    // INCLUDE "2026/09.flap" [current]
+    fn parse_include(
+        mut params: impl Iterator<Item = String>,
+        line: usize,
+    ) -> Result<Self, ParseError> {
+        let command = Command::Include;
+        let path = match params.next() {
+            Some(x) => x,
+            None => return Err(ParseError::MissingPath { line, command }),
+        };
+
+        let current = match params.next() {
+            Some(x) if x == "current" => true,
+            Some(param) => {
+                return Err(ParseError::UnexpectedParam {
+                    line,
+                    command,
+                    param,
+                })
+            }
+            None => false,
+        };
+
+        Self::expect_no_more_params(params, command, line)?;
+        Ok(Self::Include { path, current })
+    }
+
+    // RECUR monthly 1 [id=rent] DECREMENT "Checking (Bank)" 1200 rent
+    fn parse_recur(
+        mut params: impl Iterator<Item = String>,
+        line: usize,
+    ) -> Result<Self, ParseError> {
+        let (frequency, day, mut keyword) = match (params.next(), params.next(), params.next()) {
+            (Some(frequency), Some(day), Some(keyword)) => (frequency, day, keyword),
+            _ => return Err(ParseError::MissingSchedule { line }),
+        };
+
+        let id = match keyword.strip_prefix("id=") {
+            Some("") => return Err(ParseError::MissingRecurId { line }),
+            Some(id) => {
+                let id = id.to_owned();
+                keyword = params.next().ok_or(ParseError::MissingSchedule { line })?;
+                Some(id)
+            }
+            None => None,
+        };
+
+        let schedule = Frequency::parse(&frequency)
+            .zip(day.parse().ok())
+            .and_then(|(frequency, day)| Schedule::new(frequency, day))
+            .ok_or_else(|| ParseError::InvalidSchedule {
+                line,
+                schedule: format!("{} {}", frequency, day),
+            })?;
+
+        let template = Self::parse(&keyword, params.collect(), line)?;
+        match template {
+            Self::Increment { .. } | Self::Decrement { .. } | Self::Set { .. } => {}
+            _ => {
+                return Err(ParseError::InvalidRecurCommand {
+                    line,
+                    command: keyword,
+                })
+            }
+        }
+
+        Ok(Self::Recur {
+            schedule,
+            id,
+            template: Box::new(template),
+        })
+    }
+
+    // BUDGET groceries 400 [monthly]
+    fn parse_budget(
+        mut params: impl Iterator<Item = String>,
+        line: usize,
+    ) -> Result<Self, ParseError> {
+        let command = Command::Budget;
+        let name = match params.next() {
+            Some(x) => x,
+            None => return Err(ParseError::MissingWallet { line, command }),
+        };
+        let amount = Self::parse_amount(params.next(), command, line)?;
+
+        let period = match params.next() {
+            Some(x) => match Period::parse(&x) {
+                Some(period) => Some(period),
+                None => return Err(ParseError::InvalidPeriod { line, period: x }),
+            },
+            None => None,
+        };
+
+        Self::expect_no_more_params(params, command, line)?;
+        Ok(Self::Budget {
+            name,
+            amount,
+            period,
+        })
+    }
+
+    // CREATE Cash [comment] [policy=forbid] [limit=500] [key=value]...
+    fn parse_create(
+        mut params: impl Iterator<Item = String>,
+        comment_quoted: bool,
+        line: usize,
+    ) -> Result<Self, ParseError> {
+        let command = Command::Create;
+        let wallet = match params.next() {
+            Some(x) => x,
+            None => return Err(ParseError::MissingWallet { line, command }),
+        };
 
-            // if the parameter has whitespace, it needs to be surrounded by quotes
-            if param.contains(char::is_whitespace) {
-                let mut param_with_quotes = String::from('\"');
-                param_with_quotes.push_str(param);
-                param_with_quotes.push('\"');
-                combined.push_str(&param_with_quotes);
+        let mut comment = None;
+        if comment_quoted {
+            // an empty comment only keeps a quoted detail after it from being read as the comment
+            comment = params.next().filter(|x| !x.is_empty());
+        }
+        let mut policy = None;
+        let mut limit = None;
+        let mut meta = Vec::new();
+        for param in params {
+            if let Some(x) = param.strip_prefix("policy=").filter(|_| policy.is_none()) {
+                policy = Some(Self::parse_policy_name(Some(x.to_owned()), line)?);
+            } else if let Some(x) = param.strip_prefix("limit=").filter(|_| limit.is_none()) {
+                limit = Some(Self::parse_amount(Some(x.to_owned()), command, line)?);
+            } else if let Some(pair) = split_meta(&param) {
+                meta.push(pair);
+            } else if !comment_quoted
+                && comment.is_none()
+                && policy.is_none()
+                && limit.is_none()
+                && meta.is_empty()
+            {
+                comment = Some(param);
             } else {
-                combined.push_str(&param.clone());
+                return Err(ParseError::UnexpectedParam {
+                    line,
+                    command,
+                    param,
+                });
+            }
+        }
+
+        let policy = match (policy, limit) {
+            (Some(policy), limit) => Some(WalletPolicy { policy, limit }),
+            (None, Some(limit)) => {
+                return Err(ParseError::UnexpectedParam {
+                    line,
+                    command,
+                    param: format!("limit={}", limit),
+                })
+            }
+            (None, None) => None,
+        };
+
+        Ok(Self::Create {
+            wallet,
+            comment,
+            policy,
+            meta,
+        })
+    }
+
+    // META Cash institution=Chase last4=1234
+    fn parse_meta(
+        mut params: impl Iterator<Item = String>,
+        line: usize,
+    ) -> Result<Self, ParseError> {
+        let command = Command::Meta;
+        let wallet = match params.next() {
+            Some(x) => x,
+            None => return Err(ParseError::MissingWallet { line, command }),
+        };
+
+        let mut meta = Vec::new();
+        for param in params {
+            // policies are changed with POLICY
+            match split_meta(&param) {
+                Some(pair) => meta.push(pair),
+                None => {
+                    return Err(ParseError::UnexpectedParam {
+                        line,
+                        command,
+                        param,
+                    })
+                }
             }
         }
 
+        if meta.is_empty() {
+            return Err(ParseError::MissingMeta { line });
+        }
+        Ok(Self::Meta { wallet, meta })
+    }
+
+    // POLICY "Credit Card" forbid [500]
+    fn parse_policy(
+        mut params: impl Iterator<Item = String>,
+        line: usize,
+    ) -> Result<Self, ParseError> {
+        let command = Command::Policy;
+        let wallet = match params.next() {
+            Some(x) => x,
+            None => return Err(ParseError::MissingWallet { line, command }),
+        };
+        let policy = Self::parse_policy_name(params.next(), line)?;
+        let limit = match params.next() {
+            Some(x) => Some(Self::parse_amount(Some(x), command, line)?),
+            None => None,
+        };
+
+        Self::expect_no_more_params(params, command, line)?;
+        Ok(Self::Policy {
+            wallet,
+            policy: WalletPolicy { policy, limit },
+        })
+    }
+
+    fn parse_policy_name(param: Option<String>, line: usize) -> Result<Policy, ParseError> {
+        let text = match param {
+            Some(x) => x,
+            None => return Err(ParseError::MissingPolicy { line }),
+        };
+
+        match Policy::parse(&text) {
+            Some(x) => Ok(x),
+            None => Err(ParseError::InvalidPolicy { line, policy: text }),
+        }
+    }
+
+    // CHECKPOINT "log_db.2026-10-19.archive.flap" sha256:<hex>
+    fn parse_checkpoint(
+        mut params: impl Iterator<Item = String>,
+        line: usize,
+    ) -> Result<Self, ParseError> {
+        let command = Command::Checkpoint;
+        let archive = match params.next() {
+            Some(x) => x,
+            None => return Err(ParseError::MissingPath { line, command }),
+        };
+        let hash = match params.next() {
+            Some(x) => x,
+            None => return Err(ParseError::MissingHash { line }),
+        };
+
+        Self::expect_no_more_params(params, command, line)?;
+        Ok(Self::Checkpoint { archive, hash })
+    }
+
+    fn parse_amount(
+        param: Option<String>,
+        command: Command,
+        line: usize,
+    ) -> Result<Amount, ParseError> {
+        let text = match param {
+            Some(x) => x,
+            None => return Err(ParseError::MissingAmount { line, command }),
+        };
+
+        match Amount::parse(&text) {
+            Some(x) => Ok(x),
+            None => Err(ParseError::InvalidAmount { line, amount: text }),
+        }
+    }
+
+    /// Returns None for `Other` directives.
+    pub fn command(&self) -> Option<Command> {
+        match self {
+            Self::Create { .. } => Some(Command::Create),
+            Self::Increment { .. } => Some(Command::Increment),
+            Self::Decrement { .. } => Some(Command::Decrement),
+            Self::Set { .. } => Some(Command::Set),
+            Self::Destroy { .. } => Some(Command::Destroy),
+            Self::Include { .. } => Some(Command::Include),
+            Self::Checkpoint { .. } => Some(Command::Checkpoint),
+            Self::Chain => Some(Command::Chain),
+            Self::Recur { .. } => Some(Command::Recur),
+            Self::Budget { .. } => Some(Command::Budget),
+            Self::Goal { .. } => Some(Command::Goal),
+            Self::Policy { .. } => Some(Command::Policy),
+            Self::Meta { .. } => Some(Command::Meta),
+            Self::Other { .. } => None,
+        }
+    }
+
+    /// The keyword the directive starts with in the log.
+    pub fn keyword(&self) -> String {
+        match self {
+            Self::Other { command, .. } => command.clone(),
+            _ => self.command().unwrap().to_string(),
+        }
+    }
+
+    /// Returns None for directives that are not about a wallet.
+    pub fn wallet(&self) -> Option<&str> {
+        match self {
+            Self::Create { wallet, .. }
+            | Self::Increment { wallet, .. }
+            | Self::Decrement { wallet, .. }
+            | Self::Set { wallet, .. }
+            | Self::Destroy { wallet, .. }
+            | Self::Goal { wallet, .. }
+            | Self::Policy { wallet, .. }
+            | Self::Meta { wallet, .. } => Some(wallet),
+            Self::Include { .. }
+            | Self::Checkpoint { .. }
+            | Self::Chain
+            | Self::Recur { .. }
+            | Self::Budget { .. }
+            | Self::Other { .. } => None,
+        }
+    }
+
+    pub fn amount(&self) -> Option<&Amount> {
+        match self {
+            Self::Increment { amount, .. }
+            | Self::Decrement { amount, .. }
+            | Self::Set { amount, .. }
+            | Self::Goal { amount, .. } => Some(amount),
+            _ => None,
+        }
+    }
+
+    pub fn comment(&self) -> Option<&str> {
+        match self {
+            Self::Create { comment, .. }
+            | Self::Increment { comment, .. }
+            | Self::Decrement { comment, .. }
+            | Self::Set { comment, .. }
+            | Self::Destroy { comment, .. }
+            | Self::Goal { comment, .. } => comment.as_deref(),
+            Self::Include { .. }
+            | Self::Policy { .. }
+            | Self::Meta { .. }
+            | Self::Checkpoint { .. }
+            | Self::Chain
+            | Self::Recur { .. }
+            | Self::Budget { .. }
+            | Self::Other { .. } => None,
+        }
+    }
+
+    /// The params in the order they are written in the log.
+    pub fn params(&self) -> Vec<String> {
+        match self {
+            Self::Other { params, .. } => return params.clone(),
+            Self::Include { path, current } => {
+                let mut params = vec![path.clone()];
+                if *current {
+                    params.push("current".to_owned());
+                }
+                return params;
+            }
+            Self::Checkpoint { archive, hash } => return vec![archive.clone(), hash.clone()],
+            Self::Recur {
+                schedule,
+                id,
+                template,
+            } => {
+                let mut params = vec![schedule.frequency.to_string(), schedule.day.to_string()];
+                params.extend(id.as_ref().map(|x| format!("id={}", x)));
+                params.push(template.keyword());
+                params.extend(template.params());
+                return params;
+            }
+            Self::Budget {
+                name,
+                amount,
+                period,
+            } => {
+                let mut params = vec![name.clone(), amount.to_string()];
+                params.extend(period.map(|x| x.to_string()));
+                return params;
+            }
+            Self::Create {
+                wallet,
+                comment,
+                policy,
+                meta,
+            } => {
+                let mut params = vec![wallet.clone()];
+                params.extend(comment.clone());
+                if let Some(policy) = policy {
+                    params.push(format!("policy={}", policy.policy));
+                    params.extend(policy.limit.as_ref().map(|x| format!("limit={}", x)));
+                }
+                params.extend(meta.iter().map(|(key, value)| format!("{}={}", key, value)));
+                return params;
+            }
+            Self::Meta { wallet, meta } => {
+                let mut params = vec![wallet.clone()];
+                params.extend(meta.iter().map(|(key, value)| format!("{}={}", key, value)));
+                return params;
+            }
+            Self::Policy { wallet, policy } => {
+                let mut params = vec![wallet.clone(), policy.policy.to_string()];
+                params.extend(policy.limit.as_ref().map(|x| x.to_string()));
+                return params;
+            }
+            _ => {}
+        }
+
+        let mut params = Vec::new();
+        params.extend(self.wallet().map(|x| x.to_owned()));
+        params.extend(self.amount().map(|x| x.to_string()));
+        params.extend(self.comment().map(|x| x.to_owned()));
+        params
+    }
+
+    /// The params as they are written in the log, quoted where they need to be.
+    /// The comment of a CREATE is quoted if it has an `=`, so it is not read as a detail, and a
+    /// CREATE without a comment starts with an empty one if its first detail has to be quoted.
+    pub fn quoted_params(&self) -> Vec<String> {
+        let mut quoted = self
+            .params()
+            .iter()
+            .map(|x| lexer::quote(x))
+            .collect::<Vec<String>>();
+
+        if let Self::Create { comment, .. } = self {
+            match comment {
+                Some(comment) if comment.contains('=') => quoted[1] = lexer::always_quote(comment),
+                None if quoted.get(1).is_some_and(|x| x.starts_with('"')) => {
+                    quoted.insert(1, lexer::always_quote(""))
+                }
+                _ => {}
+            }
+        }
+        quoted
+    }
+
+    /// The directive as it is serialized, but with the amount written by value, so `5`, `5.0`
+    /// and `5.00` read the same.
+    pub fn canonical_text(&self) -> String {
+        let mut params = self.quoted_params();
+        if let Some(amount) = self.amount() {
+            params[1] = amount.value().to_string();
+        }
+
+        let mut text = self.keyword();
+        for param in &params {
+            text.push(' ');
+            text.push_str(param);
+        }
+        text
+    }
+
+    pub fn serialize(&self) -> String {
+        let mut combined = String::new();
+        combined.push_str(&self.keyword());
+        for param in &self.quoted_params() {
+            combined.push(' ');
+            combined.push_str(param);
+        }
+
         combined
     }
 }
 
+/// Splits a param like `institution=Chase` into its key and value.
+/// Returns None if the part before the `=` is not a key of letters, digits, `-` and `_`,
+/// or if it is `policy` or `limit`, which are the overdraft policy of a wallet rather than a detail.
+pub fn split_meta(param: &str) -> Option<(String, String)> {
+    let (key, value) = param.split_once('=')?;
+    let is_key = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+    (is_key && key != "policy" && key != "limit").then(|| (key.to_owned(), value.to_owned()))
+}
+
 #[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// A comment for use in `FlapJackStack`s.
 /// Follows the pattern Comment("# this is a comment");
 pub struct Comment {
@@ -1,113 +1,296 @@
-use regex::Regex;
+use std::env;
+use std::fmt;
 use std::fs;
+use std::path::{Path, PathBuf};
 
-use crate::flapjack_stack::flapjack::{Command, Comment, Directive, FlapJack};
-use crate::flapjack_stack::FlapJackStack;
+use crate::encryption::{self, EncryptionError, EncryptionKey};
+use crate::flapjack_stack::flapjack::{Comment, Directive, FlapJack, ParseError};
+use crate::flapjack_stack::lexer;
+use crate::flapjack_stack::store::{FileStore, LogStore, MemoryStore, StoreError};
+use crate::flapjack_stack::{FlapJackStack, LogFile};
 
 /// A builder to help create a `FlapJackStack`.
 /// This builder takes a raw string, removes carriage returns, splits it by lines,
-/// parses the lines into `Comment`s and `Directive`s, and creates a `FlapJackStack`.
+/// parses the lines into `Comment`s, `Directive`s and blank lines, and creates a `FlapJackStack`.
+/// The text of every line is kept so that rewriting the log does not reformat it.
+/// `INCLUDE` directives are read relative to the file that contains them.
 #[derive(Debug)]
 pub struct FlapJackStackBuilder {
-    lines: Vec<String>,
+    // each line is paired with its line number in the raw log
+    lines: Vec<(usize, String)>,
     log_path: Option<String>,
+    crlf: bool,
+    key: Option<EncryptionKey>,
+    passphrase: Passphrase,
+    store: Box<dyn LogStore>,
+}
+
+// keeps the passphrase out of `Debug` output
+#[derive(Default)]
+struct Passphrase(Option<String>);
+
+impl fmt::Debug for Passphrase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Passphrase(..)")
+    }
+}
+
+/// Everything read so far while following the includes of a log.
+#[derive(Default)]
+struct Parsed {
+    flapjacks: Vec<FlapJack>,
+    sources: Vec<Option<String>>,
+    origins: Vec<usize>,
+    files: Vec<LogFile>,
+    // used to spot a file that is included twice
+    canonical_paths: Vec<Option<PathBuf>>,
+    current_file: Option<usize>,
+    passphrase: Option<String>,
 }
 
 impl FlapJackStackBuilder {
     pub fn new(raw_log: &str, log_path: Option<String>) -> Self {
         // go through a parsing process
         let lines = Self::split_and_clean_raw_log(raw_log);
-        Self { lines, log_path }
+        let crlf = raw_log.contains("\r\n");
+        // a log without a path is kept in memory
+        let store: Box<dyn LogStore> = match &log_path {
+            Some(path) => Box::new(FileStore::new(path)),
+            None => Box::new(MemoryStore::new(raw_log.as_bytes())),
+        };
+        Self {
+            lines,
+            log_path,
+            crlf,
+            key: None,
+            passphrase: Passphrase::default(),
+            store,
+        }
     }
 
+    /// Encrypted logs are decrypted with the passphrase in `FLAPJACK_PASSPHRASE`.
     pub fn from_file(path: &str) -> Self {
-        let file = fs::read_to_string(path);
-        let content = file.unwrap_or_else(|_| panic!("Can't find file {}", path));
-        Self::new(&content, Some(path.to_owned()))
+        let passphrase = env::var(encryption::PASSPHRASE_VAR).ok();
+        Self::from_store(FileStore::new(path), passphrase.as_deref())
+            .unwrap_or_else(|e| panic!("Can't read file {}. {}", path, e))
     }
 
-    pub fn build(&mut self) -> FlapJackStack {
-        let mut flapjacks: Vec<FlapJack> = Vec::new();
+    /// Reads the log from a store, which the stack then writes to.
+    pub fn from_store(
+        store: impl LogStore + 'static,
+        passphrase: Option<&str>,
+    ) -> Result<Self, StoreError> {
+        let bytes = store.load().map_err(StoreError::CouldNotRead)?;
+        let log_path = store.path().map(|x| x.to_owned());
+        let mut builder = Self::from_bytes(&bytes, log_path, passphrase)?;
+        builder.store = Box::new(store);
+        Ok(builder)
+    }
+
+    /// Like `new`, but decrypts the log first if it is encrypted.
+    /// The passphrase is also used for any encrypted file the log includes.
+    pub fn from_bytes(
+        bytes: &[u8],
+        log_path: Option<String>,
+        passphrase: Option<&str>,
+    ) -> Result<Self, EncryptionError> {
+        let (raw_log, key) = Self::decode(bytes, passphrase)?;
+        let mut builder = Self::new(&raw_log, log_path);
+        builder.key = key;
+        builder.passphrase = Passphrase(passphrase.map(|x| x.to_owned()));
+        if builder.log_path.is_none() {
+            builder.store = Box::new(MemoryStore::new(bytes));
+        }
+        Ok(builder)
+    }
 
-        for line in self.lines.drain(..) {
-            // this is the regex for splitting on whitespace, unless something is in quotations
-            let mut split = Self::split_and_clean_line(&line);
+    fn decode(
+        bytes: &[u8],
+        passphrase: Option<&str>,
+    ) -> Result<(String, Option<EncryptionKey>), EncryptionError> {
+        if !encryption::is_encrypted(bytes) {
+            let raw_log =
+                String::from_utf8(bytes.to_vec()).map_err(|_| EncryptionError::InvalidText)?;
+            return Ok((raw_log, None));
+        }
+
+        let passphrase = passphrase.ok_or(EncryptionError::MissingPassphrase)?;
+        let (raw_log, key) = encryption::decrypt(bytes, passphrase)?;
+        Ok((raw_log, Some(key)))
+    }
 
-            let flapjack = match split[0].chars().next().unwrap() {
+    pub fn build(&mut self) -> Result<FlapJackStack, ParseError> {
+        let mut parsed = Parsed {
+            passphrase: self.passphrase.0.clone(),
+            ..Parsed::default()
+        };
+        let canonical_path = self
+            .log_path
+            .as_ref()
+            .and_then(|x| fs::canonicalize(x).ok());
+
+        parsed.files.push(LogFile {
+            path: self.log_path.clone(),
+            crlf: self.crlf,
+            key: self.key.clone(),
+            store: std::mem::replace(&mut self.store, Box::new(MemoryStore::default())),
+        });
+        parsed.canonical_paths.push(canonical_path.clone());
+
+        let mut ancestors: Vec<PathBuf> = canonical_path.into_iter().collect();
+        let lines = std::mem::take(&mut self.lines);
+        Self::parse_lines(lines, 0, &mut ancestors, &mut parsed)?;
+
+        let mut stack = FlapJackStack::new(parsed.flapjacks, self.log_path.clone());
+        stack.sources = parsed.sources;
+        stack.origins = parsed.origins;
+        stack.files = parsed.files;
+        stack.current_file = parsed.current_file.unwrap_or(0);
+        Ok(stack)
+    }
+
+    // `ancestors` are the files that include `file`, to catch a file including itself
+    fn parse_lines(
+        lines: Vec<(usize, String)>,
+        file: usize,
+        ancestors: &mut Vec<PathBuf>,
+        parsed: &mut Parsed,
+    ) -> Result<(), ParseError> {
+        for (line_number, line) in lines {
+            let flapjack = if line.trim().is_empty() {
+                FlapJack::Blank
+            } else if line.trim_start().starts_with('#') {
                 // line is a comment
-                '#' => {
-                    let comment = Comment::new(line.to_string());
-                    FlapJack::Comment(comment)
-                }
+                let comment = Comment::new(line.to_string());
+                FlapJack::Comment(comment)
+            } else {
                 // line is a directive
-                _ => {
-                    let command_string: &str = &split.remove(0);
-                    let command = match command_string {
-                        "CREATE" => Command::Create,
-                        "INCREMENT" => Command::Increment,
-                        "SET" => Command::Set,
-                        "DESTROY" => Command::Destroy,
-                        "DECREMENT" => Command::Decrement,
-                        _ => panic!("Command \"{}\" does not exist!", command_string),
-                    };
-                    let directive = Directive {
-                        command,
-                        params: split,
-                    };
-
-                    FlapJack::Directive(directive)
+                let tokens = lexer::tokenize(&line).map_err(|source| ParseError::Lex {
+                    line: line_number,
+                    source,
+                })?;
+                let directive = Directive::from_tokens(tokens, line_number)?;
+                FlapJack::Directive(directive)
+            };
+
+            let include = match &flapjack {
+                FlapJack::Directive(Directive::Include { path, current }) => {
+                    Some((path.clone(), *current))
                 }
+                _ => None,
             };
 
-            flapjacks.push(flapjack)
+            parsed.flapjacks.push(flapjack);
+            parsed.sources.push(Some(line));
+            parsed.origins.push(file);
+
+            if let Some((path, current)) = include {
+                Self::parse_include(&path, current, file, line_number, ancestors, parsed)?;
+            }
         }
 
-        FlapJackStack::new(flapjacks, self.log_path.clone())
+        Ok(())
     }
 
-    fn split_and_clean_line(line: &str) -> Vec<String> {
-        let re = Regex::new(r#"[^\s"']+|"([^"]*)"|'([^']*)'"#).unwrap();
-        let mut split = re
-            .find_iter(line)
-            .map(|chunk| chunk.as_str().to_owned())
-            .collect::<Vec<String>>();
+    fn parse_include(
+        path: &str,
+        current: bool,
+        including_file: usize,
+        line: usize,
+        ancestors: &mut Vec<PathBuf>,
+        parsed: &mut Parsed,
+    ) -> Result<(), ParseError> {
+        let including_dir = parsed.files[including_file]
+            .path
+            .as_ref()
+            .and_then(|x| Path::new(x).parent())
+            .unwrap_or_else(|| Path::new(""));
+        let resolved = including_dir.join(path);
 
-        // remove any quotes left
-        for part in split.iter_mut() {
-            let split_on_quotes = part.split('\"');
-            *part = split_on_quotes.collect();
+        let could_not_read = |e: std::io::Error| ParseError::CouldNotReadInclude {
+            line,
+            path: path.to_owned(),
+            kind: e.kind(),
+        };
+        let canonical = fs::canonicalize(&resolved).map_err(could_not_read)?;
+
+        if ancestors.contains(&canonical) {
+            return Err(ParseError::IncludeCycle {
+                line,
+                path: path.to_owned(),
+            });
+        }
+        if parsed.canonical_paths.contains(&Some(canonical.clone())) {
+            return Err(ParseError::DuplicateInclude {
+                line,
+                path: path.to_owned(),
+            });
         }
-        split
-    }
 
-    fn split_and_clean_raw_log(raw_log: &str) -> Vec<String> {
-        let no_carriage_returns = Self::remove_carriage_returns(raw_log);
-        let split = no_carriage_returns.split('\n').collect::<Vec<&str>>();
-        let mut cleaned: Vec<String> = Vec::new();
+        let store = FileStore::new(&resolved.to_string_lossy());
+        let bytes = store.load().map_err(could_not_read)?;
+        let (raw, key) = Self::decode(&bytes, parsed.passphrase.as_deref()).map_err(|source| {
+            ParseError::CouldNotDecryptInclude {
+                line,
+                path: path.to_owned(),
+                source,
+            }
+        })?;
+        let resolved = resolved.to_string_lossy().to_string();
+
+        let file = parsed.files.len();
+        parsed.files.push(LogFile {
+            path: Some(resolved.clone()),
+            crlf: raw.contains("\r\n"),
+            key,
+            store: Box::new(store),
+        });
+        parsed.canonical_paths.push(Some(canonical.clone()));
 
-        for line in split {
-            if Self::remove_whitespace(line).is_empty() {
-                continue;
+        if current {
+            if parsed.current_file.is_some() {
+                return Err(ParseError::MultipleCurrentFiles { line });
             }
-            cleaned.push(line.to_owned());
+            parsed.current_file = Some(file);
         }
 
-        cleaned
+        ancestors.push(canonical);
+        let lines = Self::split_and_clean_raw_log(&raw);
+        Self::parse_lines(lines, file, ancestors, parsed).map_err(|e| match e {
+            // errors from files included further down already say where they are
+            e @ ParseError::InFile { .. } => e,
+            e => ParseError::InFile {
+                file: resolved,
+                source: Box::new(e),
+            },
+        })?;
+        ancestors.pop();
+
+        Ok(())
     }
 
-    fn remove_carriage_returns(s: &str) -> String {
-        s.split('\r').collect()
+    fn split_and_clean_raw_log(raw_log: &str) -> Vec<(usize, String)> {
+        let no_carriage_returns = Self::remove_carriage_returns(raw_log);
+        let split = no_carriage_returns.split('\n');
+
+        split
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.to_owned()))
+            .collect()
     }
 
-    fn remove_whitespace(s: &str) -> String {
-        s.split_whitespace().collect()
+    fn remove_carriage_returns(s: &str) -> String {
+        s.split('\r').collect()
     }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::flapjack_stack::flapjack::{Command, Comment, Directive, FlapJack};
+    use crate::flapjack_stack::flapjack::{
+        Amount, Command, Comment, Directive, FlapJack, ParseError,
+    };
     use crate::flapjack_stack::flapjack_stack_builder::FlapJackStackBuilder;
+    use proptest::prelude::*;
 
     #[test]
     fn test_for_carriage_return_discrimination() {
@@ -131,10 +314,11 @@ mod test {
     #[test]
     fn test_builder() {
         let log = "# the program will register this line a comment
-            CREATE account \"Checking (Bank)\"
-            CREATE account \"Savings (Bank)\"";
+            CREATE \"Checking (Bank)\"
+            INCREMENT \"Checking (Bank)\" 25.50 \"got paid\"
+            REMIND groceries 400 monthly";
 
-        let stack = FlapJackStackBuilder::new(log, None).build();
+        let stack = FlapJackStackBuilder::new(log, None).build().unwrap();
 
         assert_eq!(
             stack.flapjacks[0],
@@ -145,18 +329,238 @@ mod test {
 
         assert_eq!(
             stack.flapjacks[1],
-            FlapJack::Directive(Directive {
-                command: Command::Create,
-                params: vec!["account".to_owned(), "Checking (Bank)".to_owned()]
+            FlapJack::Directive(Directive::Create {
+                wallet: "Checking (Bank)".to_owned(),
+                comment: None,
+                policy: None,
+                meta: Vec::new(),
+            })
+        );
+
+        assert_eq!(
+            stack.flapjacks[2],
+            FlapJack::Directive(Directive::Increment {
+                wallet: "Checking (Bank)".to_owned(),
+                amount: Amount::parse("25.50").unwrap(),
+                comment: Some("got paid".to_owned())
             })
         );
 
+        // unknown commands are kept as they are
+        assert_eq!(
+            stack.flapjacks[3],
+            FlapJack::Directive(Directive::Other {
+                command: "REMIND".to_owned(),
+                params: vec![
+                    "groceries".to_owned(),
+                    "400".to_owned(),
+                    "monthly".to_owned()
+                ]
+            })
+        );
+        assert_eq!(stack.serialize(), log);
+    }
+
+    #[test]
+    fn test_blank_lines_and_trailing_comments() {
+        let log = "CREATE Cash\n\n    INCREMENT Cash 5 \"lunch\"   # paid back later\n";
+        let stack = FlapJackStackBuilder::new(log, None).build().unwrap();
+
+        assert_eq!(stack.flapjacks.len(), 4);
+        assert_eq!(stack.flapjacks[1], FlapJack::Blank);
         assert_eq!(
             stack.flapjacks[2],
-            FlapJack::Directive(Directive {
-                command: Command::Create,
-                params: vec!["account".to_owned(), "Savings (Bank)".to_owned()]
+            FlapJack::Directive(Directive::Increment {
+                wallet: "Cash".to_owned(),
+                amount: Amount::new(5.0),
+                comment: Some("lunch".to_owned())
             })
         );
+        assert_eq!(stack.flapjacks[3], FlapJack::Blank);
+        assert_eq!(stack.serialize(), log);
+    }
+
+    #[test]
+    fn test_builder_errors() {
+        let log = "CREATE \"Checking (Bank)\"\n\nINCREMENT \"Checking (Bank)\" lots";
+        let error = FlapJackStackBuilder::new(log, None).build().unwrap_err();
+
+        assert_eq!(
+            error,
+            ParseError::InvalidAmount {
+                line: 3,
+                amount: "lots".to_owned()
+            }
+        );
+    }
+
+    #[test]
+    fn test_include_errors() {
+        let error = FlapJackStackBuilder::new("CREATE Cash\nINCLUDE", None)
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            error,
+            ParseError::MissingPath {
+                line: 2,
+                command: Command::Include
+            }
+        );
+
+        let error = FlapJackStackBuilder::new("INCLUDE old.flap later", None)
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            error,
+            ParseError::UnexpectedParam {
+                line: 1,
+                command: Command::Include,
+                param: "later".to_owned()
+            }
+        );
+    }
+
+    #[test]
+    fn test_wallet_metadata() {
+        let log = r#"CREATE Checking "main account" institution=Chase last4=1234
+META Checking last4=9876 "notes=joint with Sam"
+META Checking institution=
+CREATE Cash
+META Cash opened=2019-04-01
+DESTROY Cash
+CREATE Cash"#;
+        let stack = FlapJackStackBuilder::new(log, None).build().unwrap();
+
+        assert_eq!(
+            stack.flapjacks[0],
+            FlapJack::Directive(Directive::Create {
+                wallet: "Checking".to_owned(),
+                comment: Some("main account".to_owned()),
+                policy: None,
+                meta: vec![
+                    ("institution".to_owned(), "Chase".to_owned()),
+                    ("last4".to_owned(), "1234".to_owned())
+                ],
+            })
+        );
+
+        let details = &stack.db.metadata["Checking"];
+        assert_eq!(details.get("institution"), None);
+        assert_eq!(details.get("last4").map(|x| x.as_str()), Some("9876"));
+        assert_eq!(
+            details.get("notes").map(|x| x.as_str()),
+            Some("joint with Sam")
+        );
+        // a new wallet with the name of a destroyed one starts without details
+        assert!(!stack.db.metadata.contains_key("Cash"));
+        assert_eq!(stack.serialize(), log);
+
+        let error = FlapJackStackBuilder::new("CREATE Cash\nMETA Cash policy=forbid", None)
+            .build()
+            .unwrap_err();
+        assert_eq!(
+            error,
+            ParseError::UnexpectedParam {
+                line: 2,
+                command: Command::Meta,
+                param: "policy=forbid".to_owned()
+            }
+        );
+    }
+
+    #[test]
+    fn test_awkward_params_round_trip() {
+        let log = r##"CREATE a'b
+INCREMENT a'b 5 "she said \"hi\""
+DECREMENT a'b 1 "#not a comment"
+SET a'b 2 ""
+CREATE 'My Wallet'
+INCREMENT 'My Wallet' 5 "'a quote'"
+CREATE ""
+SET "" 0"##;
+
+        let stack = FlapJackStackBuilder::new(log, None).build().unwrap();
+
+        assert_eq!(
+            stack.flapjacks[3],
+            FlapJack::Directive(Directive::Set {
+                wallet: "a'b".to_owned(),
+                amount: Amount::new(2.0),
+                comment: Some("".to_owned())
+            })
+        );
+        assert_eq!(stack.serialize(), log);
+    }
+
+    #[test]
+    fn test_create_comment_with_equals_sign() {
+        let create = |comment: Option<&str>, meta: &[(&str, &str)]| Directive::Create {
+            wallet: "Savings".to_owned(),
+            comment: comment.map(|x| x.to_owned()),
+            policy: None,
+            meta: meta
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+        };
+        let cases = [
+            (create(Some("rate=5"), &[]), "CREATE Savings \"rate=5\""),
+            (
+                create(Some("policy=forbid"), &[("bank", "Chase")]),
+                "CREATE Savings \"policy=forbid\" bank=Chase",
+            ),
+            (
+                create(None, &[("notes", "joint with Sam")]),
+                "CREATE Savings \"\" \"notes=joint with Sam\"",
+            ),
+        ];
+
+        for (directive, serialized) in cases {
+            assert_eq!(directive.serialize(), serialized);
+            let stack = FlapJackStackBuilder::new(serialized, None).build().unwrap();
+            assert_eq!(stack.flapjacks, vec![FlapJack::Directive(directive)]);
+        }
+
+        // a bare param that looks like a detail is still a detail
+        let stack = FlapJackStackBuilder::new("CREATE Savings rate=5", None)
+            .build()
+            .unwrap();
+        assert_eq!(
+            stack.flapjacks,
+            vec![FlapJack::Directive(create(None, &[("rate", "5")]))]
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn directive_round_trips(
+            wallet in any::<String>(),
+            create_comment in proptest::option::of(".+"),
+            meta in proptest::collection::vec(("[a-z0-9_-]{1,8}", any::<String>()), 0..3),
+            comment in proptest::option::of(any::<String>()),
+            cents in any::<i32>(),
+        ) {
+            let meta = meta
+                .into_iter()
+                .filter(|(key, _)| key != "policy" && key != "limit")
+                .collect();
+            let create = Directive::Create {
+                wallet: wallet.clone(),
+                comment: create_comment,
+                policy: None,
+                meta,
+            };
+            let increment = Directive::Increment {
+                wallet,
+                amount: Amount::new(cents as f64 / 100.0),
+                comment,
+            };
+            let serialized = format!("{}\n{}", create.serialize(), increment.serialize());
+
+            let stack = FlapJackStackBuilder::new(&serialized, None).build().unwrap();
+            prop_assert_eq!(&stack.flapjacks[0], &FlapJack::Directive(create));
+            prop_assert_eq!(&stack.flapjacks[1], &FlapJack::Directive(increment));
+            prop_assert_eq!(stack.serialize(), serialized);
+        }
     }
 }
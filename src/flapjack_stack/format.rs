@@ -0,0 +1,322 @@
+//! The canonical layout written by `flapjack fmt`.
+
+use super::flapjack::{Amount, Directive, FlapJack};
+use super::{lexer, FlapJackStack};
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FormatOptions {
+    /// Sort the CREATE directives at the top of the log by wallet name.
+    pub sort_creates: bool,
+}
+
+// a line of the log while it is being formatted
+type Line = (FlapJack, Option<String>);
+
+impl FlapJackStack {
+    /// Rewrites every line in the canonical layout, which `serialize` then writes out.
+    /// The columns of consecutive directives are aligned, amounts get at least two decimal places,
+    /// params are only quoted when they need to be, comment lines lose their indentation and
+    /// runs of blank lines become a single blank line. A file that is not empty ends with a new line.
+    /// Every included file is formatted on its own.
+    pub fn format(&mut self, options: FormatOptions) {
+        // the files each file includes, in order
+        let mut children: Vec<Vec<usize>> = vec![Vec::new(); self.files.len()];
+        for file in 1..self.files.len() {
+            let first_line = self.origins.iter().position(|x| *x == file);
+            if let Some(first_line) = first_line.filter(|x| *x > 0) {
+                children[self.origins[first_line - 1]].push(file);
+            }
+        }
+
+        let mut per_file: Vec<Vec<Line>> = vec![Vec::new(); self.files.len()];
+        let lines = self
+            .flapjacks
+            .drain(..)
+            .zip(self.sources.drain(..))
+            .zip(self.origins.drain(..));
+        for (line, origin) in lines {
+            per_file[origin].push(line);
+        }
+
+        let mut formatted = per_file
+            .into_iter()
+            .map(|x| format_file(x, options).into_iter())
+            .collect::<Vec<_>>();
+        let mut children = children
+            .into_iter()
+            .map(|x| x.into_iter())
+            .collect::<Vec<_>>();
+
+        self.push_formatted(0, &mut formatted, &mut children);
+    }
+
+    // puts the formatted files back together, with each included file after its INCLUDE directive
+    fn push_formatted(
+        &mut self,
+        file: usize,
+        formatted: &mut [std::vec::IntoIter<(FlapJack, String)>],
+        children: &mut [std::vec::IntoIter<usize>],
+    ) {
+        while let Some((flapjack, source)) = formatted[file].next() {
+            let is_include = matches!(flapjack, FlapJack::Directive(Directive::Include { .. }));
+
+            self.flapjacks.push(flapjack);
+            self.sources.push(Some(source));
+            self.origins.push(file);
+
+            if !is_include {
+                continue;
+            }
+            if let Some(child) = children[file].next() {
+                self.push_formatted(child, formatted, children);
+            }
+        }
+    }
+}
+
+fn format_file(mut lines: Vec<Line>, options: FormatOptions) -> Vec<(FlapJack, String)> {
+    if options.sort_creates {
+        sort_leading_creates(&mut lines);
+    }
+
+    let mut kept: Vec<Line> = Vec::new();
+    for line in lines {
+        let previous_is_blank = kept.last().is_none_or(|(x, _)| *x == FlapJack::Blank);
+        if line.0 == FlapJack::Blank && previous_is_blank {
+            continue;
+        }
+        kept.push(line);
+    }
+    if kept.last().is_some_and(|(x, _)| *x == FlapJack::Blank) {
+        kept.pop();
+    }
+
+    let mut formatted: Vec<String> = Vec::new();
+    let mut start = 0;
+    while start < kept.len() {
+        let block_length = kept[start..]
+            .iter()
+            .take_while(|(x, _)| matches!(x, FlapJack::Directive(_)))
+            .count();
+
+        if block_length > 0 {
+            formatted.extend(format_block(&kept[start..start + block_length]));
+            start += block_length;
+            continue;
+        }
+
+        match &kept[start].0 {
+            FlapJack::Comment(comment) => formatted.push(comment.string.trim().to_owned()),
+            _ => formatted.push(String::new()),
+        }
+        start += 1;
+    }
+
+    if !kept.is_empty() {
+        kept.push((FlapJack::Blank, None));
+        formatted.push(String::new());
+    }
+
+    kept.into_iter().map(|(x, _)| x).zip(formatted).collect()
+}
+
+/// The first run of CREATE directives in the log, which usually sets up every wallet.
+fn sort_leading_creates(lines: &mut [Line]) {
+    let start = match lines
+        .iter()
+        .position(|(x, _)| matches!(x, FlapJack::Directive(_)))
+    {
+        Some(x) => x,
+        None => return,
+    };
+
+    let length = lines[start..]
+        .iter()
+        .take_while(|(x, _)| matches!(x, FlapJack::Directive(Directive::Create { .. })))
+        .count();
+
+    lines[start..start + length].sort_by(|(a, _), (b, _)| wallet_name(a).cmp(wallet_name(b)));
+}
+
+fn wallet_name(flapjack: &FlapJack) -> &str {
+    match flapjack {
+        FlapJack::Directive(directive) => directive.wallet().unwrap_or_default(),
+        _ => "",
+    }
+}
+
+/// Formats consecutive directives so their keyword, wallet and amount columns line up.
+/// Every directive about a wallet has the wallet first in its params, followed by its amount if it has one.
+/// Directives without a wallet are written as they are and do not take part in the alignment.
+fn format_block(lines: &[Line]) -> Vec<String> {
+    let rows: Vec<Option<[String; 3]>> = lines
+        .iter()
+        .map(|(flapjack, _)| match flapjack {
+            FlapJack::Directive(directive) if directive.wallet().is_some() => Some([
+                directive.keyword(),
+                lexer::quote(directive.wallet().unwrap_or_default()),
+                directive.amount().map(normalize_amount).unwrap_or_default(),
+            ]),
+            _ => None,
+        })
+        .collect();
+
+    let mut widths = [0; 3];
+    for row in rows.iter().flatten() {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let texts: Vec<String> = lines
+        .iter()
+        .zip(&rows)
+        .map(|((flapjack, _), row)| {
+            let (row, directive) = match (row, flapjack) {
+                (Some(row), FlapJack::Directive(directive)) => (row, directive),
+                (_, flapjack) => return flapjack.serialize(),
+            };
+
+            let mut cells = Vec::new();
+            for (i, (width, cell)) in widths.iter().zip(row).enumerate() {
+                if *width == 0 {
+                    continue;
+                }
+                // amounts are right aligned so the decimal points line up
+                if i == 2 {
+                    cells.push(format!("{:>width$}", cell, width = width));
+                } else {
+                    cells.push(format!("{:<width$}", cell, width = width));
+                }
+            }
+            // the params after the wallet and amount, like the comment, are written as they are
+            let aligned = if directive.amount().is_some() { 2 } else { 1 };
+            cells.extend(directive.quoted_params().into_iter().skip(aligned));
+
+            cells.join(" ").trim_end().to_owned()
+        })
+        .collect();
+
+    let trailing_comments: Vec<Option<String>> = lines
+        .iter()
+        .map(|(_, source)| {
+            let tokens = lexer::tokenize(source.as_deref()?).ok()?;
+            tokens.trailing_comment.map(|x| x.trim_end().to_owned())
+        })
+        .collect();
+
+    let comment_column = texts
+        .iter()
+        .map(|x| x.chars().count())
+        .max()
+        .unwrap_or_default();
+
+    texts
+        .into_iter()
+        .zip(trailing_comments)
+        .map(|(text, comment)| match comment {
+            Some(comment) => format!("{:<width$} {}", text, comment, width = comment_column),
+            None => text,
+        })
+        .collect()
+}
+
+/// Writes the amount with at least two decimal places, and more only if they are needed to keep its value.
+fn normalize_amount(amount: &Amount) -> String {
+    let shortest = amount.value().to_string();
+    let decimals = shortest.split_once('.').map_or(0, |(_, x)| x.len());
+    format!("{:.*}", decimals.max(2), amount.value())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FormatOptions;
+    use crate::flapjack_stack::flapjack::{Directive, FlapJack};
+    use crate::flapjack_stack::flapjack_stack_builder::FlapJackStackBuilder;
+
+    fn format(log: &str, options: FormatOptions) -> String {
+        let mut stack = FlapJackStackBuilder::new(log, None).build().unwrap();
+        stack.format(options);
+        stack.serialize()
+    }
+
+    #[test]
+    fn test_format() {
+        let log = "\n\n   # wallets\nCREATE \"Savings (Bank)\"\nCREATE   Cash  # the jar\n\n\n\nINCREMENT Cash 5 \"lunch money\"\nDECREMENT \"Savings (Bank)\" 120.5\nSET Cash 0.125   # rounding\nBUDGET  groceries 400\n\n";
+
+        let expected = "# wallets
+CREATE \"Savings (Bank)\"
+CREATE Cash             # the jar
+
+INCREMENT Cash               5.00 \"lunch money\"
+DECREMENT \"Savings (Bank)\" 120.50
+SET       Cash              0.125               # rounding
+BUDGET groceries 400
+";
+
+        let formatted = format(log, FormatOptions::default());
+        assert_eq!(formatted, expected);
+        // formatting a formatted log changes nothing
+        assert_eq!(format(&formatted, FormatOptions::default()), expected);
+    }
+
+    // the keyword and params of every directive, with amounts compared by value since fmt rewrites them
+    fn directives(log: &str) -> Vec<(String, Vec<String>)> {
+        let stack = FlapJackStackBuilder::new(log, None).build().unwrap();
+        stack
+            .flapjacks
+            .iter()
+            .filter_map(|x| match x {
+                FlapJack::Directive(directive) => Some(directive),
+                _ => None,
+            })
+            .map(|directive: &Directive| {
+                let mut params = directive.params();
+                if let Some(amount) = directive.amount() {
+                    params[1] = amount.value().to_string();
+                }
+                (directive.keyword(), params)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_format_keeps_every_param() {
+        // INCLUDE is left out since it needs a file to read
+        let log = r#"CREATE Cash "the jar" policy=forbid limit=50
+CREATE Card institution=Chase "notes=joint with Sam"
+POLICY Card warn 20
+META Card last4=1234 institution=
+CHAIN
+INCREMENT Cash 5 "lunch money"
+DECREMENT Cash 1.5
+SET Card -3
+GOAL Cash 500 "new bike"
+BUDGET groceries 400 weekly
+RECUR monthly 1 DECREMENT Card 12 phone
+CHECKPOINT old.flap sha256:00
+REMIND Cash "count it"
+DESTROY Card
+"#;
+
+        let formatted = format(log, FormatOptions::default());
+        assert_eq!(directives(&formatted), directives(log));
+    }
+
+    #[test]
+    fn test_sort_creates() {
+        let log = "# wallets\nCREATE b\nCREATE a # first\nINCREMENT b 1\nCREATE c";
+        let options = FormatOptions { sort_creates: true };
+
+        assert_eq!(
+            format(log, options),
+            "# wallets\nCREATE    a      # first\nCREATE    b\nINCREMENT b 1.00\nCREATE    c\n"
+        );
+    }
+
+    #[test]
+    fn test_empty_log() {
+        assert_eq!(format("", FormatOptions::default()), "");
+        assert_eq!(format("\n\n", FormatOptions::default()), "");
+    }
+}
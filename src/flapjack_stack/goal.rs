@@ -0,0 +1,154 @@
+//! Savings goals and how close each wallet is to its goal.
+
+use chrono::{Days, NaiveDate};
+
+use super::flapjack::{Directive, FlapJack};
+use super::{FlapJackDb, FlapJackStack};
+use crate::import::split_date;
+
+/// A goal together with the balance of its wallet.
+#[derive(Debug, PartialEq, Clone)]
+pub struct GoalStatus {
+    pub wallet: String,
+    pub target: f64,
+    pub description: Option<String>,
+    pub balance: f64,
+    /// When the goal is reached if the wallet keeps growing at its average rate so far.
+    /// None if the goal is reached already, or the wallet has not been growing.
+    pub projected: Option<NaiveDate>,
+}
+
+impl GoalStatus {
+    /// The share of the target that has been saved, as a percentage.
+    pub fn percent(&self) -> f64 {
+        if self.target == 0.0 {
+            return 100.0;
+        }
+        self.balance / self.target * 100.0
+    }
+
+    pub fn is_reached(&self) -> bool {
+        self.balance >= self.target
+    }
+}
+
+impl FlapJackStack {
+    /// The goal of every wallet that has one. A later GOAL for the same wallet replaces the earlier one.
+    /// The average rate is the change in balance from the wallet's first dated directive to `today`.
+    pub fn goals(&self, today: NaiveDate) -> Vec<GoalStatus> {
+        let mut goals: Vec<GoalStatus> = Vec::new();
+        for flapjack in &self.flapjacks {
+            if let FlapJack::Directive(Directive::Goal {
+                wallet,
+                amount,
+                comment,
+            }) = flapjack
+            {
+                let balance = match self.db.wallet_amounts.get(wallet) {
+                    Some(x) => *x,
+                    None => continue,
+                };
+                let status = GoalStatus {
+                    wallet: wallet.clone(),
+                    target: amount.value(),
+                    description: comment.clone(),
+                    balance,
+                    projected: None,
+                };
+                match goals.iter_mut().find(|x| x.wallet == *wallet) {
+                    Some(goal) => *goal = status,
+                    None => goals.push(status),
+                }
+            }
+        }
+
+        for goal in goals.iter_mut() {
+            if goal.is_reached() {
+                continue;
+            }
+            let (start, start_balance) = match self.first_dated_balance(&goal.wallet) {
+                Some(x) => x,
+                None => continue,
+            };
+
+            let days = (today - start).num_days();
+            let per_day = (goal.balance - start_balance) / days as f64;
+            if days <= 0 || per_day <= 0.0 {
+                continue;
+            }
+            let days_left = ((goal.target - goal.balance) / per_day).ceil() as u64;
+            goal.projected = today.checked_add_days(Days::new(days_left));
+        }
+
+        goals
+    }
+
+    /// The latest GOAL of every wallet that is still open. A checkpoint keeps these lines.
+    pub(super) fn goal_lines(&self) -> Vec<usize> {
+        let mut lines: Vec<usize> = Vec::new();
+        for (i, flapjack) in self.flapjacks.iter().enumerate() {
+            let wallet = match flapjack {
+                FlapJack::Directive(Directive::Goal { wallet, .. }) => wallet,
+                _ => continue,
+            };
+            if !self.db.wallet_amounts.contains_key(wallet) {
+                continue;
+            }
+            // a later GOAL for the same wallet replaces the earlier one
+            lines.retain(|x| !is_goal_of(&self.flapjacks[*x], wallet));
+            lines.push(i);
+        }
+        lines
+    }
+
+    /// The date of the first dated directive on the wallet, and the wallet's balance right before it.
+    /// If that directive is a SET, the balance it sets is where the wallet started instead.
+    pub(super) fn first_dated_balance(&self, wallet: &str) -> Option<(NaiveDate, f64)> {
+        let mut db = FlapJackDb::from_flaps(&Vec::new());
+        for flapjack in &self.flapjacks {
+            if let FlapJack::Directive(directive) = flapjack {
+                let date = directive.comment().and_then(|x| split_date(x).0);
+                if let (Some(date), Some(directive_wallet)) = (date, directive.wallet()) {
+                    if directive_wallet == wallet {
+                        if let Directive::Set { amount, .. } = directive {
+                            return Some((date, amount.value()));
+                        }
+                        let balance = db.wallet_amounts.get(wallet).copied().unwrap_or_default();
+                        return Some((date, balance));
+                    }
+                }
+            }
+            db.update(flapjack);
+        }
+        None
+    }
+}
+
+fn is_goal_of(flapjack: &FlapJack, wallet: &str) -> bool {
+    matches!(flapjack, FlapJack::Directive(Directive::Goal { wallet: x, .. }) if x == wallet)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::flapjack_stack::flapjack_stack_builder::FlapJackStackBuilder;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_goals() {
+        let log = r#"CREATE Savings
+INCREMENT Savings 1000
+GOAL Savings 5000 "emergency fund"
+INCREMENT Savings 500 "2026-09-01 paycheck"
+INCREMENT Savings 500 "2026-10-01 paycheck""#;
+        let stack = FlapJackStackBuilder::new(log, None).build().unwrap();
+        let today = NaiveDate::from_ymd_opt(2026, 10, 31).unwrap();
+
+        let goals = stack.goals(today);
+        assert_eq!(goals.len(), 1);
+        assert_eq!(goals[0].balance, 2000.0);
+        assert_eq!(goals[0].percent(), 40.0);
+        assert_eq!(goals[0].description.as_deref(), Some("emergency fund"));
+        // 1000 in the 60 days since 2026-09-01, so the last 3000 take 180 days
+        assert_eq!(goals[0].projected, NaiveDate::from_ymd_opt(2027, 4, 29));
+    }
+}
@@ -0,0 +1,103 @@
+//! Wallets named like `Bank:Checking` are in the group `Bank`, and groups can be nested.
+
+use super::FlapJackDb;
+
+pub const GROUP_SEPARATOR: char = ':';
+
+/// Whether the wallet is the group itself or anywhere inside it.
+pub fn in_group(wallet: &str, group: &str) -> bool {
+    wallet
+        .strip_prefix(group)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(GROUP_SEPARATOR))
+}
+
+/// The groups a wallet is in, outermost first. `Bank:Savings:Joint` is in `Bank` and `Bank:Savings`.
+pub fn groups_of(wallet: &str) -> Vec<&str> {
+    wallet
+        .match_indices(GROUP_SEPARATOR)
+        .map(|(i, _)| &wallet[..i])
+        .filter(|x| !x.is_empty())
+        .collect()
+}
+
+/// Sorts names so that every group comes right before what is inside it.
+/// Sorting the names as plain text would put `Bank Card` between `Bank` and `Bank:Checking`.
+pub fn sort_as_tree(names: &mut [String]) {
+    names.sort_by(|a, b| a.split(GROUP_SEPARATOR).cmp(b.split(GROUP_SEPARATOR)));
+}
+
+impl FlapJackDb {
+    /// Every group that has a wallet in it, sorted as a tree.
+    pub fn groups(&self) -> Vec<String> {
+        let mut groups = self
+            .wallet_amounts
+            .keys()
+            .flat_map(|x| groups_of(x))
+            .map(|x| x.to_owned())
+            .collect::<Vec<String>>();
+        sort_as_tree(&mut groups);
+        groups.dedup();
+        groups
+    }
+
+    /// The names of the wallets in the group, sorted as a tree.
+    pub fn wallet_names_in(&self, group: &str) -> Vec<String> {
+        let mut names = self
+            .wallet_amounts
+            .keys()
+            .filter(|x| in_group(x, group))
+            .cloned()
+            .collect::<Vec<String>>();
+        sort_as_tree(&mut names);
+        names
+    }
+
+    /// The sum of every wallet in the group.
+    pub fn subtotal(&self, group: &str) -> f64 {
+        self.wallet_amounts
+            .iter()
+            .filter(|(name, _)| in_group(name, group))
+            .map(|(_, amount)| amount)
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{groups_of, in_group};
+    use crate::flapjack_stack::flapjack_stack_builder::FlapJackStackBuilder;
+
+    #[test]
+    fn test_in_group() {
+        assert!(in_group("Bank:Checking", "Bank"));
+        assert!(in_group("Bank:Savings:Joint", "Bank:Savings"));
+        assert!(in_group("Bank", "Bank"));
+        assert!(!in_group("Banking", "Bank"));
+        assert_eq!(
+            groups_of("Bank:Savings:Joint"),
+            vec!["Bank", "Bank:Savings"]
+        );
+        assert!(groups_of("Cash").is_empty());
+    }
+
+    #[test]
+    fn test_subtotals() {
+        let log = r#"CREATE Bank:Checking
+CREATE Bank:Savings:Joint
+CREATE "Bank Card"
+CREATE Cash
+SET Bank:Checking 255
+SET Bank:Savings:Joint 720
+SET "Bank Card" -40
+SET Cash 58"#;
+        let stack = FlapJackStackBuilder::new(log, None).build().unwrap();
+
+        assert_eq!(stack.db.groups(), vec!["Bank", "Bank:Savings"]);
+        assert_eq!(stack.db.subtotal("Bank"), 975.0);
+        assert_eq!(stack.db.subtotal("Bank:Savings"), 720.0);
+        assert_eq!(
+            stack.db.wallet_names_in("Bank"),
+            vec!["Bank:Checking", "Bank:Savings:Joint"]
+        );
+    }
+}
@@ -0,0 +1,119 @@
+//! Balances at an earlier point in the log.
+
+use chrono::NaiveDate;
+
+use super::flapjack::FlapJack;
+use super::{FlapJackDb, FlapJackStack};
+use crate::import::{split_date, DATE_FORMAT};
+
+/// A point in the log to replay the directives up to.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Position {
+    /// Up to and including this line of the log itself. Files included before it count in full.
+    Line(usize),
+    /// Up to the first directive whose comment starts with a later date.
+    /// Directives without a date take the date of the directive before them.
+    Date(NaiveDate),
+}
+
+impl Position {
+    /// Reads a date like 2026-06-30 or a line number.
+    pub fn parse(input: &str) -> Option<Self> {
+        let input = input.trim();
+        if let Ok(date) = NaiveDate::parse_from_str(input, DATE_FORMAT) {
+            return Some(Self::Date(date));
+        }
+        input.parse().ok().map(Self::Line)
+    }
+}
+
+impl FlapJackStack {
+    /// The number of flapjacks at the start of the stack that come before `position`.
+    pub fn cutoff(&self, position: Position) -> usize {
+        let mut line = 0;
+
+        for (i, flapjack) in self.flapjacks.iter().enumerate() {
+            let past = match position {
+                Position::Line(last_line) => {
+                    if self.origins[i] != 0 {
+                        continue;
+                    }
+                    line += 1;
+                    line > last_line
+                }
+                Position::Date(last_date) => match flapjack {
+                    FlapJack::Directive(directive) => directive
+                        .comment()
+                        .and_then(|x| split_date(x).0)
+                        .is_some_and(|x| x > last_date),
+                    _ => false,
+                },
+            };
+
+            if past {
+                return i;
+            }
+        }
+
+        self.flapjacks.len()
+    }
+
+    /// The balances as they were at `position`.
+    pub fn db_at(&self, position: Position) -> FlapJackDb {
+        let mut db = FlapJackDb::from_flaps(&Vec::new());
+        for flapjack in &self.flapjacks[..self.cutoff(position)] {
+            db.update(flapjack);
+        }
+        db
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Position;
+    use crate::flapjack_stack::flapjack_stack_builder::FlapJackStackBuilder;
+    use chrono::NaiveDate;
+
+    const LOG: &str = r#"CREATE Cash
+INCREMENT Cash 10 "2026-06-01 paycheck"
+DECREMENT Cash 2 lunch
+# July
+DECREMENT Cash 3 "2026-07-02 bus"
+SET Cash 100"#;
+
+    #[test]
+    fn test_db_at_line() {
+        let stack = FlapJackStackBuilder::new(LOG, None).build().unwrap();
+
+        assert_eq!(
+            stack.db_at(Position::Line(0)).wallet_amounts.get("Cash"),
+            None
+        );
+        assert_eq!(
+            stack.db_at(Position::Line(3)).wallet_amounts.get("Cash"),
+            Some(&8.0)
+        );
+        assert_eq!(
+            stack.db_at(Position::Line(500)).wallet_amounts,
+            stack.db.wallet_amounts
+        );
+    }
+
+    #[test]
+    fn test_db_at_date() {
+        let stack = FlapJackStackBuilder::new(LOG, None).build().unwrap();
+        let june_30 = NaiveDate::from_ymd_opt(2026, 6, 30).unwrap();
+
+        // the undated lunch takes the date of the paycheck before it
+        assert_eq!(
+            stack
+                .db_at(Position::Date(june_30))
+                .wallet_amounts
+                .get("Cash"),
+            Some(&8.0)
+        );
+        assert_eq!(Position::parse("2026-06-30"), Some(Position::Date(june_30)));
+        assert_eq!(Position::parse(" 12 "), Some(Position::Line(12)));
+        assert_eq!(Position::parse("June"), None);
+    }
+}
@@ -0,0 +1,217 @@
+//! Splits a directive line into its keyword and params, and quotes params on the way back out.
+//!
+//! A param is either a bare word (anything up to the next whitespace) or a double quoted string.
+//! Inside quotes, `\"`, `\\`, `\n`, `\r` and `\t` are escapes. Any other backslash is kept as is,
+//! so older logs with paths like `"C:\logs"` still read the same.
+//! Older versions also read a single quoted string as one param, quotes and all, so a param that
+//! starts with `'` still runs to the next `'` and keeps both of them.
+//! An unquoted `#` at the start of a param begins a comment that runs to the end of the line.
+
+#[derive(thiserror::Error, Debug, PartialEq, Clone)]
+pub enum LexError {
+    #[error("A quote opened at column {column} is never closed.")]
+    UnterminatedQuote { column: usize },
+}
+
+/// The params of a line and the comment that follows them, if any.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Tokens {
+    pub params: Vec<String>,
+    /// Whether each param was written in quotes.
+    pub quoted: Vec<bool>,
+    /// Includes the leading `#`.
+    pub trailing_comment: Option<String>,
+}
+
+/// Splits a line on whitespace, keeping quoted strings together.
+pub fn tokenize(line: &str) -> Result<Tokens, LexError> {
+    let mut tokens = Tokens::default();
+    let mut chars = line.chars().enumerate().peekable();
+
+    while let Some(&(column, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        if c == '#' {
+            tokens.trailing_comment = Some(chars.map(|(_, c)| c).collect());
+            break;
+        }
+
+        let mut token = String::new();
+        // only a param that starts with a single quote looks ahead for the closing one
+        let single_quoted = match c {
+            '\'' => {
+                let mut quoted = String::from('\'');
+                let closed = chars.clone().skip(1).any(|(_, c)| {
+                    quoted.push(c);
+                    c == '\''
+                });
+                closed.then_some(quoted)
+            }
+            _ => None,
+        };
+
+        if let Some(single_quoted) = &single_quoted {
+            token.push_str(single_quoted);
+            chars.nth(single_quoted.chars().count() - 1);
+        } else if c == '"' {
+            chars.next();
+            let mut closed = false;
+
+            while let Some((_, c)) = chars.next() {
+                match c {
+                    '"' => {
+                        closed = true;
+                        break;
+                    }
+                    '\\' => match chars.peek().map(|(_, c)| *c) {
+                        Some(escaped @ ('"' | '\\')) => {
+                            token.push(escaped);
+                            chars.next();
+                        }
+                        Some('n') => {
+                            token.push('\n');
+                            chars.next();
+                        }
+                        Some('r') => {
+                            token.push('\r');
+                            chars.next();
+                        }
+                        Some('t') => {
+                            token.push('\t');
+                            chars.next();
+                        }
+                        _ => token.push('\\'),
+                    },
+                    c => token.push(c),
+                }
+            }
+
+            if !closed {
+                return Err(LexError::UnterminatedQuote { column: column + 1 });
+            }
+        } else {
+            while let Some(&(_, c)) = chars.peek() {
+                if c.is_whitespace() {
+                    break;
+                }
+                token.push(c);
+                chars.next();
+            }
+        }
+
+        tokens.params.push(token);
+        tokens.quoted.push(c == '"' || single_quoted.is_some());
+    }
+
+    Ok(tokens)
+}
+
+/// Returns the param as it should be written in the log.
+/// Params are only quoted when they would not survive `tokenize` as a bare word.
+pub fn quote(param: &str) -> String {
+    let needs_quotes = param.is_empty()
+        || param.starts_with('#')
+        || param.starts_with('\'')
+        || param.contains(|c: char| c.is_whitespace() || c == '"' || c == '\\');
+
+    if !needs_quotes {
+        return param.to_owned();
+    }
+    always_quote(param)
+}
+
+/// Returns the param in quotes, even if it would survive `tokenize` as a bare word.
+pub fn always_quote(param: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in param.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{quote, tokenize, LexError};
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_tokenize() {
+        let tokens =
+            tokenize(r#"INCREMENT "Checking (Bank)" 50 "she said \"hi\"" a'b """#).unwrap();
+        assert_eq!(
+            tokens.params,
+            vec![
+                "INCREMENT",
+                "Checking (Bank)",
+                "50",
+                "she said \"hi\"",
+                "a'b",
+                ""
+            ]
+        );
+    }
+
+    #[test]
+    fn test_single_quotes() {
+        let tokens = tokenize(r#"CREATE 'My Wallet' 'a "b"'c Sam's"#).unwrap();
+        assert_eq!(
+            tokens.params,
+            vec!["CREATE", "'My Wallet'", "'a \"b\"'", "c", "Sam's"]
+        );
+        assert_eq!(tokens.quoted, vec![false, true, true, false, false]);
+        // without a closing quote it is a bare word
+        assert_eq!(
+            tokenize("SET 'Cash 20").unwrap().params,
+            vec!["SET", "'Cash", "20"]
+        );
+        assert_eq!(quote("'Cash"), "\"'Cash\"");
+    }
+
+    #[test]
+    fn test_unknown_escape_is_kept() {
+        assert_eq!(tokenize(r#""C:\logs""#).unwrap().params, vec![r"C:\logs"]);
+    }
+
+    #[test]
+    fn test_trailing_comment() {
+        let tokens = tokenize(r##"INCREMENT Cash 5 "#1 lunch" # paid by card"##).unwrap();
+        assert_eq!(tokens.params, vec!["INCREMENT", "Cash", "5", "#1 lunch"]);
+        assert_eq!(tokens.trailing_comment, Some("# paid by card".to_owned()));
+    }
+
+    #[test]
+    fn test_unterminated_quote() {
+        assert_eq!(
+            tokenize("SET \"Cash 20"),
+            Err(LexError::UnterminatedQuote { column: 5 })
+        );
+    }
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("Cash"), "Cash");
+        assert_eq!(quote(""), "\"\"");
+        assert_eq!(quote("#1"), "\"#1\"");
+        assert_eq!(quote("a \"b\""), "\"a \\\"b\\\"\"");
+    }
+
+    proptest! {
+        #[test]
+        fn quote_round_trips(params in proptest::collection::vec(any::<String>(), 0..5)) {
+            let line = params.iter().map(|x| quote(x)).collect::<Vec<String>>().join(" ");
+            prop_assert!(!line.contains('\n'));
+            prop_assert_eq!(tokenize(&line).unwrap().params, params);
+        }
+    }
+}
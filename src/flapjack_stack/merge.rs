@@ -0,0 +1,399 @@
+//! Three-way merge of two copies of a log that both had directives appended to them.
+//!
+//! Only the log itself is merged. The lines of included files are left out.
+//! In a chained log the appended directives are hashed again in their merged order.
+
+use std::collections::{BTreeSet, HashMap};
+
+use chrono::NaiveDate;
+
+use super::chain::without_chain_hash;
+use super::flapjack::{Directive, FlapJack};
+use super::{lexer, FlapJackStack};
+use crate::import::split_date;
+
+#[derive(thiserror::Error, Debug, PartialEq, Clone)]
+pub enum MergeError {
+    #[error("Line {line} of {side} differs from the base. Only logs that were appended to can be merged.")]
+    HistoryChanged { side: &'static str, line: usize },
+}
+
+/// The merged log, with a conflict block for every wallet in `conflicts`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Merged {
+    pub log: String,
+    /// Wallets that one side SET, CREATEd or DESTROYed while the other side also changed them.
+    pub conflicts: Vec<String>,
+}
+
+// a line of the log and its text
+type Line<'a> = (&'a FlapJack, String);
+
+// a directive together with the comments and blank lines before it
+struct Chunk<'a> {
+    lines: Vec<Line<'a>>,
+    date: Option<NaiveDate>,
+}
+
+impl Chunk<'_> {
+    fn directive(&self) -> Option<&Directive> {
+        match self.lines.last()?.0 {
+            FlapJack::Directive(x) => Some(x),
+            _ => None,
+        }
+    }
+
+    fn wallet(&self) -> Option<&str> {
+        self.directive()?.wallet()
+    }
+}
+
+/// Merges the directives `ours` and `theirs` appended to `base`.
+/// The appended directives are interleaved by the date their comment starts with, keeping each
+/// side in its own order, and ours go first when the dates do not decide.
+pub fn merge(
+    base: &FlapJackStack,
+    ours: &FlapJackStack,
+    theirs: &FlapJackStack,
+) -> Result<Merged, MergeError> {
+    let base_lines = log_lines(base);
+    let our_lines = log_lines(ours);
+    let their_lines = log_lines(theirs);
+
+    let line_ending = if ours.files[0].crlf { "\r\n" } else { "\n" };
+    let texts = |lines: &[Line]| lines.iter().map(|(_, x)| x.clone()).collect::<Vec<_>>();
+    let finish = |mut texts: Vec<String>, conflicts: Vec<String>| {
+        // the log ends with a new line
+        texts.push(String::new());
+        Merged {
+            log: texts.join(line_ending),
+            conflicts,
+        }
+    };
+
+    // one side did not change anything
+    if texts(&their_lines) == texts(&base_lines) || texts(&their_lines) == texts(&our_lines) {
+        return Ok(finish(texts(&our_lines), Vec::new()));
+    }
+    if texts(&our_lines) == texts(&base_lines) {
+        return Ok(finish(texts(&their_lines), Vec::new()));
+    }
+
+    let our_chunks = chunks(appended(&base_lines, &our_lines, "ours")?);
+    let their_chunks = chunks(appended(&base_lines, &their_lines, "theirs")?);
+    let conflicts = conflicting_wallets(&our_chunks, &their_chunks);
+
+    let in_conflict = |chunk: &Chunk| {
+        chunk
+            .wallet()
+            .is_some_and(|x| conflicts.iter().any(|y| y == x))
+    };
+    let (our_conflicts, our_chunks): (Vec<Chunk>, Vec<Chunk>) =
+        our_chunks.into_iter().partition(|x| in_conflict(x));
+    let (their_conflicts, their_chunks): (Vec<Chunk>, Vec<Chunk>) =
+        their_chunks.into_iter().partition(|x| in_conflict(x));
+
+    let mut interleaved: Vec<Line> = Vec::new();
+    let mut ours = our_chunks.into_iter().peekable();
+    let mut theirs = their_chunks.into_iter().peekable();
+    loop {
+        let take_theirs = match (ours.peek(), theirs.peek()) {
+            (Some(our), Some(their)) => matches!(
+                (our.date, their.date),
+                (Some(our_date), Some(their_date)) if their_date < our_date
+            ),
+            (None, Some(_)) => true,
+            (Some(_), None) => false,
+            (None, None) => break,
+        };
+        let chunk = if take_theirs {
+            theirs.next()
+        } else {
+            ours.next()
+        };
+        interleaved.extend(chunk.expect("The chunk was peeked").lines);
+    }
+
+    let mut merged = texts(&base_lines);
+    merged.extend(rechain(&base_lines, &interleaved));
+    let before_conflicts = [base_lines, interleaved].concat();
+
+    // every conflicting wallet gets a block with what each side did to it
+    // each side is chained after the lines before the blocks, so either one can be kept
+    for wallet in &conflicts {
+        let side = |chunks: &[Chunk]| {
+            let lines = chunks
+                .iter()
+                .filter(|x| x.wallet() == Some(wallet.as_str()))
+                .flat_map(|x| x.lines.iter().cloned())
+                .collect::<Vec<_>>();
+            rechain(&before_conflicts, &lines)
+        };
+        merged.push("<<<<<<< ours".to_owned());
+        merged.extend(side(&our_conflicts));
+        merged.push("=======".to_owned());
+        merged.extend(side(&their_conflicts));
+        merged.push(">>>>>>> theirs".to_owned());
+    }
+
+    Ok(finish(merged, conflicts))
+}
+
+/// The lines of the log itself, without the blank lines at its end.
+fn log_lines(stack: &FlapJackStack) -> Vec<Line<'_>> {
+    let mut lines: Vec<Line> = stack
+        .flapjacks
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| stack.origins[*i] == 0)
+        .map(|(i, flapjack)| {
+            let text = match stack.source(i) {
+                Some(x) => x.to_owned(),
+                None => flapjack.serialize(),
+            };
+            (flapjack, text)
+        })
+        .collect();
+
+    while lines.last().is_some_and(|(x, _)| **x == FlapJack::Blank) {
+        lines.pop();
+    }
+    lines
+}
+
+/// The text of lines appended after `before`. If the log is chained by then, the hash of every
+/// directive is worked out again, since the directive before it may have changed.
+fn rechain(before: &[Line], appended: &[Line]) -> Vec<String> {
+    let flapjacks = before.iter().map(|(x, _)| (*x).clone()).collect();
+    let mut stack = FlapJackStack::new(flapjacks, None);
+    stack.sources = before.iter().map(|(_, x)| Some(x.clone())).collect();
+
+    let mut texts = Vec::new();
+    for (flapjack, text) in appended {
+        let index = stack.flapjacks.len();
+        let text = if stack.is_chained() && matches!(flapjack, FlapJack::Directive(_)) {
+            let note = lexer::tokenize(text)
+                .ok()
+                .and_then(|x| x.trailing_comment)
+                .and_then(|x| without_chain_hash(&x));
+            stack
+                .new_source(index, flapjack, note.as_deref())
+                .unwrap_or_else(|| flapjack.serialize())
+        } else {
+            text.clone()
+        };
+
+        stack.flapjacks.push((*flapjack).clone());
+        stack.sources.push(Some(text.clone()));
+        stack.origins.push(0);
+        texts.push(text);
+    }
+    texts
+}
+
+/// The lines a side added after the base, which it has to start with.
+fn appended<'a>(
+    base: &[Line],
+    side: &[Line<'a>],
+    name: &'static str,
+) -> Result<Vec<Line<'a>>, MergeError> {
+    for (i, (base_line, side_line)) in base.iter().zip(side).enumerate() {
+        if base_line.1 != side_line.1 {
+            return Err(MergeError::HistoryChanged {
+                side: name,
+                line: i + 1,
+            });
+        }
+    }
+    if side.len() < base.len() {
+        return Err(MergeError::HistoryChanged {
+            side: name,
+            line: side.len() + 1,
+        });
+    }
+
+    Ok(side[base.len()..].to_vec())
+}
+
+fn chunks(lines: Vec<Line>) -> Vec<Chunk> {
+    let mut chunks = Vec::new();
+    let mut current = Vec::new();
+    // a directive without a date happened on the date of the one before it
+    let mut date = None;
+
+    for line in lines {
+        let is_directive = matches!(line.0, FlapJack::Directive(_));
+        if let FlapJack::Directive(directive) = line.0 {
+            if let Some(own_date) = directive.comment().and_then(|x| split_date(x).0) {
+                date = Some(own_date);
+            }
+        }
+
+        current.push(line);
+        if is_directive {
+            chunks.push(Chunk {
+                lines: std::mem::take(&mut current),
+                date,
+            });
+        }
+    }
+
+    if !current.is_empty() {
+        chunks.push(Chunk {
+            lines: current,
+            date,
+        });
+    }
+    chunks
+}
+
+/// Wallets both sides changed, where at least one of them SET, CREATEd or DESTROYed it.
+/// A CREATE of a wallet that already exists starts it over at zero, like a SET.
+fn conflicting_wallets(ours: &[Chunk], theirs: &[Chunk]) -> Vec<String> {
+    // whether each wallet was overwritten by a side
+    let touched = |chunks: &[Chunk]| {
+        let mut wallets: HashMap<String, bool> = HashMap::new();
+        for directive in chunks.iter().filter_map(|x| x.directive()) {
+            if let Some(wallet) = directive.wallet() {
+                let overwrites = matches!(
+                    directive,
+                    Directive::Set { .. } | Directive::Create { .. } | Directive::Destroy { .. }
+                );
+                *wallets.entry(wallet.to_owned()).or_default() |= overwrites;
+            }
+        }
+        wallets
+    };
+
+    let ours = touched(ours);
+    let theirs = touched(theirs);
+    let conflicts: BTreeSet<String> = ours
+        .iter()
+        .filter_map(|(wallet, our_overwrite)| {
+            let their_overwrite = theirs.get(wallet)?;
+            (*our_overwrite || *their_overwrite).then(|| wallet.clone())
+        })
+        .collect();
+
+    conflicts.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{merge, MergeError};
+    use crate::flapjack_stack::flapjack::ParseError;
+    use crate::flapjack_stack::flapjack_stack_builder::FlapJackStackBuilder;
+    use crate::flapjack_stack::FlapJackStack;
+
+    fn stack(log: &str) -> FlapJackStack {
+        FlapJackStackBuilder::new(log, None).build().unwrap()
+    }
+
+    const BASE: &str = "CREATE Cash\nCREATE Jar\n";
+
+    #[test]
+    fn test_appended_directives_are_interleaved_by_date() {
+        let ours = format!(
+            "{}INCREMENT Cash 5 \"2026-06-01 paycheck\"\nDECREMENT Cash 1 coffee\nINCREMENT Cash 2 \"2026-06-05 refund\"\n",
+            BASE
+        );
+        let theirs = format!("{}# lunch\nDECREMENT Cash 3 \"2026-06-03 lunch\"\n", BASE);
+
+        let merged = merge(&stack(BASE), &stack(&ours), &stack(&theirs)).unwrap();
+        assert_eq!(merged.conflicts, Vec::<String>::new());
+        assert_eq!(
+            merged.log,
+            format!(
+                "{}INCREMENT Cash 5 \"2026-06-01 paycheck\"\nDECREMENT Cash 1 coffee\n# lunch\nDECREMENT Cash 3 \"2026-06-03 lunch\"\nINCREMENT Cash 2 \"2026-06-05 refund\"\n",
+                BASE
+            )
+        );
+    }
+
+    #[test]
+    fn test_overwriting_both_sides_is_a_conflict() {
+        let ours = format!("{}SET Jar 10\nINCREMENT Cash 1\n", BASE);
+        let theirs = format!("{}INCREMENT Jar 2\n", BASE);
+
+        let merged = merge(&stack(BASE), &stack(&ours), &stack(&theirs)).unwrap();
+        assert_eq!(merged.conflicts, vec!["Jar".to_owned()]);
+        assert_eq!(
+            merged.log,
+            format!(
+                "{}INCREMENT Cash 1\n<<<<<<< ours\nSET Jar 10\n=======\nINCREMENT Jar 2\n>>>>>>> theirs\n",
+                BASE
+            )
+        );
+
+        // the log can not be read until the conflict is resolved
+        assert_eq!(
+            FlapJackStackBuilder::new(&merged.log, None)
+                .build()
+                .unwrap_err(),
+            ParseError::ConflictMarker { line: 4 }
+        );
+    }
+
+    #[test]
+    fn test_creating_again_is_a_conflict() {
+        let ours = format!("{}CREATE Jar\n", BASE);
+        let theirs = format!("{}INCREMENT Jar 2\n", BASE);
+
+        let merged = merge(&stack(BASE), &stack(&ours), &stack(&theirs)).unwrap();
+        assert_eq!(merged.conflicts, vec!["Jar".to_owned()]);
+        assert!(merged
+            .log
+            .ends_with("<<<<<<< ours\nCREATE Jar\n=======\nINCREMENT Jar 2\n>>>>>>> theirs\n"));
+    }
+
+    #[test]
+    fn test_changed_history_is_an_error() {
+        let ours = "CREATE Wallet\nCREATE Jar\nINCREMENT Jar 1";
+        let theirs = format!("{}INCREMENT Jar 2\n", BASE);
+
+        assert_eq!(
+            merge(&stack(BASE), &stack(ours), &stack(&theirs)),
+            Err(MergeError::HistoryChanged {
+                side: "ours",
+                line: 1
+            })
+        );
+        // one side that did not change anything is not a merge at all
+        assert_eq!(
+            merge(&stack(BASE), &stack(ours), &stack(BASE)).unwrap().log,
+            format!("{}\n", ours)
+        );
+    }
+
+    #[test]
+    fn test_merged_chain_still_verifies() {
+        let base = "CREATE Cash\nCHAIN\n";
+        let side = |push: &dyn Fn(&mut FlapJackStack)| {
+            let mut stack = stack(base);
+            push(&mut stack);
+            stack.serialize()
+        };
+        let ours = side(&|x| {
+            x.increment_wallet_amount("Cash", 5.0, Some("2026-06-02 paycheck"))
+                .unwrap()
+        });
+        let theirs = side(&|x| {
+            x.decrement_wallet_amount("Cash", 3.0, Some("2026-06-01 lunch"))
+                .unwrap()
+        });
+
+        // their directive goes first, so ours is linked to it instead of to CHAIN
+        let merged = merge(&stack(base), &stack(&ours), &stack(&theirs)).unwrap();
+        assert!(merged.log.find("lunch") < merged.log.find("paycheck"));
+        assert_eq!(stack(&merged.log).verify(), Ok(2));
+
+        // either side of a conflict can be kept
+        let ours = side(&|x| x.set_wallet_amount("Cash", 10.0, None).unwrap());
+        let merged = merge(&stack(base), &stack(&ours), &stack(&theirs)).unwrap();
+        let lines = merged.log.lines().collect::<Vec<&str>>();
+        let keep_ours = [&lines[..2], &lines[3..4]].concat().join("\n");
+        let keep_theirs = [&lines[..2], &lines[5..6]].concat().join("\n");
+        assert_eq!(stack(&keep_ours).verify(), Ok(1));
+        assert_eq!(stack(&keep_theirs).verify(), Ok(1));
+    }
+}
@@ -1,66 +1,186 @@
-use std::collections::HashMap;
-use std::fs::OpenOptions;
-use std::io::Write;
-
-use self::flapjack::{Command, Directive, FlapJack};
-
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+
+use self::flapjack::{Amount, Directive, FlapJack};
+use self::policy::{Overdraft, Policy, WalletPolicy};
+use self::store::{FileStore, LogStore, MemoryStore};
+use crate::encryption::{self, EncryptionKey};
+
+pub mod budget;
+pub mod chain;
+pub mod checkpoint;
+pub mod diff;
 pub mod flapjack;
 pub mod flapjack_stack_builder;
+pub mod format;
+pub mod goal;
+pub mod group;
+pub mod history;
+pub mod lexer;
+pub mod merge;
+pub mod policy;
+pub mod schedule;
+pub mod store;
+
+/// Why a flapjack was not added to the log. Nothing is added when either happens.
+#[derive(thiserror::Error, Debug)]
+pub enum PushError {
+    #[error(transparent)]
+    Overdraft(#[from] Overdraft),
+    #[error("Could not write to the log. ({0})")]
+    Write(#[from] io::Error),
+}
 
-/// A sequence of `Flap`s that each contain either a `Directive` or a `Comment`.
-/// Each flap in the sequence retains its order.
+/// A sequence of `Flap`s that each contain either a `Directive`, a `Comment` or a blank line.
+/// Each flap in the sequence retains its order. The lines of included files come right after
+/// the `INCLUDE` directive that read them.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FlapJackStack {
     pub flapjacks: Vec<FlapJack>,
     pub db: FlapJackDb,
     pub log_path: Option<String>,
+    /// The original text of each flapjack, in the same order as `flapjacks`.
+    /// Lines with a source are written back exactly as they were read; flapjacks without one are rendered.
+    pub sources: Vec<Option<String>>,
+    /// The file each flapjack was read from, as an index into `files`.
+    pub origins: Vec<usize>,
+    /// The log itself, followed by every file it includes.
+    pub files: Vec<LogFile>,
+    /// The file new flapjacks are written to, as an index into `files`.
+    pub current_file: usize,
+}
+
+/// A file that makes up part of a `FlapJackStack`.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LogFile {
+    pub path: Option<String>,
+    /// Whether the file was read with `\r\n` line endings.
+    pub crlf: bool,
+    /// The key the file is encrypted with. Encrypted files are encrypted again whenever they are written.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub key: Option<EncryptionKey>,
+    /// Where the file is written. A log without a path is kept in memory.
+    #[cfg_attr(feature = "serde", serde(skip, default = "store::default_store"))]
+    pub store: Box<dyn LogStore>,
 }
 
 impl FlapJackStack {
     pub fn new(flapjacks: Vec<FlapJack>, log_path: Option<String>) -> Self {
         let db = FlapJackDb::from_flaps(&flapjacks);
-        Self {
+        let sources = vec![None; flapjacks.len()];
+        let origins = vec![0; flapjacks.len()];
+        let store: Box<dyn LogStore> = match &log_path {
+            Some(path) => Box::new(FileStore::new(path)),
+            None => Box::new(MemoryStore::default()),
+        };
+        let files = vec![LogFile {
+            path: log_path.clone(),
+            crlf: false,
+            key: None,
+            store,
+        }];
+        let mut stack = Self {
             flapjacks,
             db,
             log_path,
+            sources,
+            origins,
+            files,
+            current_file: 0,
+        };
+
+        if stack.log_path.is_none() {
+            stack.files[0].store = Box::new(MemoryStore::new(stack.serialize().as_bytes()));
         }
+        stack
     }
 
-    pub fn serialize_to_file(&self, path: &str) {
-        let mut file = OpenOptions::new()
-            .create(true)
-            .write(true)
-            .open(path)
-            .unwrap();
+    pub fn serialize_to_file(&self, path: &str) -> io::Result<()> {
+        FileStore::new(path).rewrite(&self.file_bytes(0))
+    }
 
-        let serialized = self.serialize();
-        file.write_all(serialized.as_bytes()).unwrap();
+    /// Writes one of `files` back to its store.
+    pub fn write_file(&mut self, file: usize) -> io::Result<()> {
+        let bytes = self.file_bytes(file);
+        self.files[file].store.rewrite(&bytes)
     }
 
-    pub fn serialize(&self) -> String {
-        let mut serialized = String::new();
-        for (i, flapjack) in self.flapjacks.iter().enumerate() {
-            serialized.push_str(&flapjack.serialize());
+    /// One of `files` as it is stored, which is encrypted if the file has a key.
+    pub fn file_bytes(&self, file: usize) -> Vec<u8> {
+        let serialized = self.serialize_file(file);
+        match &self.files[file].key {
+            Some(key) => encryption::encrypt(&serialized, key),
+            None => serialized.into_bytes(),
+        }
+    }
 
-            // add a new line if it is not the last line
-            if (i + 1) != self.flapjacks.len() {
-                serialized.push('\n');
+    // appends to the store when the file only grew at its end, and rewrites it otherwise
+    fn write_pushed_flap(&mut self, file: usize, before: &str) -> io::Result<()> {
+        let after = self.serialize_file(file);
+        match after.strip_prefix(before) {
+            Some(added) if self.files[file].key.is_none() => {
+                self.files[file].store.append(added.as_bytes())
             }
+            _ => self.write_file(file),
         }
-        serialized
     }
 
-    pub fn return_wallet_names(&self) -> Vec<String> {
-        let mut names = Vec::new();
+    /// Serializes the log itself, leaving out the lines of included files.
+    pub fn serialize(&self) -> String {
+        self.serialize_file(0)
+    }
 
-        for wallet_name in self.db.wallet_amounts.keys() {
-            names.push(wallet_name.to_owned());
-        }
+    /// Serializes one of `files`.
+    pub fn serialize_file(&self, file: usize) -> String {
+        let line_ending = if self.files[file].crlf { "\r\n" } else { "\n" };
+
+        let lines = self
+            .flapjacks
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| self.origins[*i] == file)
+            .map(|(i, flapjack)| match self.source(i) {
+                Some(source) => source.to_owned(),
+                None => flapjack.serialize(),
+            })
+            .collect::<Vec<String>>();
+
+        lines.join(line_ending)
+    }
 
-        // Sort the names alphabetically
-        names.sort();
+    /// The line the flapjack at `index` is on in its own file.
+    pub fn line_number(&self, index: usize) -> usize {
+        let file = self.origins[index];
+        self.origins[..index].iter().filter(|x| **x == file).count() + 1
+    }
 
-        names
+    /// The text the flapjack at `index` was read from, if it has not been replaced since.
+    pub fn source(&self, index: usize) -> Option<&str> {
+        self.sources.get(index).and_then(|x| x.as_deref())
+    }
+
+    /// Replaces the flapjack at `index`, which will be rendered the next time the log is written.
+    pub fn replace_flap(&mut self, index: usize, flapjack: FlapJack) {
+        self.flapjacks[index] = flapjack;
+        self.sources[index] = None;
+        self.db = FlapJackDb::from_flaps(&self.flapjacks);
+    }
+
+    /// The `# comment` written after the directive at `index`, including the `#`.
+    pub fn trailing_comment(&self, index: usize) -> Option<String> {
+        match self.flapjacks.get(index) {
+            Some(FlapJack::Directive(_)) => {
+                let tokens = lexer::tokenize(self.source(index)?).ok()?;
+                tokens.trailing_comment
+            }
+            _ => None,
+        }
+    }
+
+    pub fn return_wallet_names(&self) -> Vec<String> {
+        self.db.wallet_names()
     }
 
     pub fn amount(&self, wallet_name: &str) -> f64 {
@@ -73,26 +193,91 @@ impl FlapJackStack {
         *amount
     }
 
-    // updates the flap to the db and writes to file
-    pub fn push_flap(&mut self, flapjack: FlapJack) {
-        self.db.update(&flapjack);
-        self.flapjacks.push(flapjack);
-        self.serialize_to_file(&self.log_path.clone().expect("This should not happen!"));
+    // updates the flap to the db and writes it to the end of the current file
+    // nothing is written if the flap would overdraw a wallet that forbids it
+    pub fn push_flap(&mut self, flapjack: FlapJack) -> Result<(), PushError> {
+        Self::check_forbidden(&self.db, &flapjack)?;
+        Ok(self.insert_flap(flapjack, None)?)
     }
 
-    pub fn set_wallet_amount(&mut self, wallet_name: &str, amount: f64, comment: Option<&str>) {
-        let flapjack = match comment {
-            Some(x) => FlapJack::Directive(Directive {
-                command: Command::Set,
-                params: vec![wallet_name.to_owned(), amount.to_string(), x.to_owned()],
-            }),
-            None => FlapJack::Directive(Directive {
-                command: Command::Set,
-                params: vec![wallet_name.to_owned(), amount.to_string()],
-            }),
+    /// Like `push_flap`, but writes `note` in a trailing comment after the flapjack.
+    pub fn push_flap_with_note(&mut self, flapjack: FlapJack, note: &str) -> Result<(), PushError> {
+        Self::check_forbidden(&self.db, &flapjack)?;
+        Ok(self.insert_flap(flapjack, Some(note))?)
+    }
+
+    /// Checks flapjacks that would be pushed one after another, without pushing any of them.
+    /// Returns the first one that would overdraw a wallet that forbids it, or else the overdrafts
+    /// of wallets that only warn.
+    pub fn check_batch<'a>(
+        &self,
+        flapjacks: impl IntoIterator<Item = &'a FlapJack>,
+    ) -> Result<Vec<Overdraft>, Overdraft> {
+        let mut db = self.db.clone();
+        let mut warnings = Vec::new();
+        for flapjack in flapjacks {
+            match db.check_policy(flapjack) {
+                Some(overdraft) if overdraft.policy == Policy::Forbid => return Err(overdraft),
+                Some(overdraft) => warnings.push(overdraft),
+                None => {}
+            }
+            db.update(flapjack);
+        }
+        Ok(warnings)
+    }
+
+    // wallets that only warn are left to whoever asked for the flap
+    fn check_forbidden(db: &FlapJackDb, flapjack: &FlapJack) -> Result<(), Overdraft> {
+        match db.check_policy(flapjack) {
+            Some(overdraft) if overdraft.policy == Policy::Forbid => Err(overdraft),
+            _ => Ok(()),
+        }
+    }
+
+    // the flap is only kept if it could be written, so the stack never holds more than the file
+    fn insert_flap(&mut self, flapjack: FlapJack, note: Option<&str>) -> io::Result<()> {
+        let current = self.current_file;
+        let before = self.serialize_file(current);
+        let index = match self.origins.iter().rposition(|x| *x == current) {
+            // a file that ends with a new line keeps it after the new flap
+            Some(last)
+                if self.flapjacks[last] == FlapJack::Blank && self.source(last) == Some("") =>
+            {
+                last
+            }
+            Some(last) => last + 1,
+            None => self.flapjacks.len(),
         };
 
-        self.push_flap(flapjack);
+        // a chained log gets the hash written after the new directive
+        let source = self.new_source(index, &flapjack, note);
+        self.flapjacks.insert(index, flapjack);
+        self.sources.insert(index, source);
+        self.origins.insert(index, current);
+
+        if let Err(e) = self.write_pushed_flap(current, &before) {
+            self.flapjacks.remove(index);
+            self.sources.remove(index);
+            self.origins.remove(index);
+            return Err(e);
+        }
+        self.db.update(&self.flapjacks[index]);
+        Ok(())
+    }
+
+    pub fn set_wallet_amount(
+        &mut self,
+        wallet_name: &str,
+        amount: f64,
+        comment: Option<&str>,
+    ) -> Result<(), PushError> {
+        let flapjack = FlapJack::Directive(Directive::Set {
+            wallet: wallet_name.to_owned(),
+            amount: Amount::new(amount),
+            comment: comment.map(|x| x.to_owned()),
+        });
+
+        self.push_flap(flapjack)
     }
 
     pub fn decrement_wallet_amount(
@@ -100,19 +285,14 @@ impl FlapJackStack {
         wallet_name: &str,
         amount: f64,
         comment: Option<&str>,
-    ) {
-        let flapjack = match comment {
-            Some(x) => FlapJack::Directive(Directive {
-                command: Command::Decrement,
-                params: vec![wallet_name.to_owned(), amount.to_string(), x.to_owned()],
-            }),
-            None => FlapJack::Directive(Directive {
-                command: Command::Decrement,
-                params: vec![wallet_name.to_owned(), amount.to_string()],
-            }),
-        };
+    ) -> Result<(), PushError> {
+        let flapjack = FlapJack::Directive(Directive::Decrement {
+            wallet: wallet_name.to_owned(),
+            amount: Amount::new(amount),
+            comment: comment.map(|x| x.to_owned()),
+        });
 
-        self.push_flap(flapjack);
+        self.push_flap(flapjack)
     }
 
     pub fn increment_wallet_amount(
@@ -120,106 +300,153 @@ impl FlapJackStack {
         wallet_name: &str,
         amount: f64,
         comment: Option<&str>,
-    ) {
-        let flapjack = match comment {
-            Some(x) => FlapJack::Directive(Directive {
-                command: Command::Increment,
-                params: vec![wallet_name.to_owned(), amount.to_string(), x.to_owned()],
-            }),
-            None => FlapJack::Directive(Directive {
-                command: Command::Increment,
-                params: vec![wallet_name.to_owned(), amount.to_string()],
-            }),
-        };
+    ) -> Result<(), PushError> {
+        let flapjack = FlapJack::Directive(Directive::Increment {
+            wallet: wallet_name.to_owned(),
+            amount: Amount::new(amount),
+            comment: comment.map(|x| x.to_owned()),
+        });
 
-        self.push_flap(flapjack);
+        self.push_flap(flapjack)
     }
 
-    pub fn create_wallet(&mut self, wallet_name: &str) {
-        let flapjack = FlapJack::Directive(Directive {
-            command: Command::Create,
-            params: vec![wallet_name.to_owned()],
+    // a new or destroyed wallet can not be overdrawn, so these only fail to write
+    pub fn create_wallet(
+        &mut self,
+        wallet_name: &str,
+        policy: Option<WalletPolicy>,
+        meta: Vec<(String, String)>,
+    ) -> io::Result<()> {
+        let flapjack = FlapJack::Directive(Directive::Create {
+            wallet: wallet_name.to_owned(),
+            comment: None,
+            policy,
+            meta,
+        });
+        self.insert_flap(flapjack, None)
+    }
+
+    /// Writes a META directive. A key with an empty value is removed from the wallet.
+    pub fn set_wallet_meta(
+        &mut self,
+        wallet_name: &str,
+        meta: Vec<(String, String)>,
+    ) -> io::Result<()> {
+        let flapjack = FlapJack::Directive(Directive::Meta {
+            wallet: wallet_name.to_owned(),
+            meta,
         });
-        self.push_flap(flapjack);
+        self.insert_flap(flapjack, None)
     }
 
-    pub fn destroy_wallet(&mut self, wallet_name: &str) {
-        let flapjack = FlapJack::Directive(Directive {
-            command: Command::Destroy,
-            params: vec![wallet_name.to_owned()],
+    pub fn destroy_wallet(&mut self, wallet_name: &str) -> io::Result<()> {
+        let flapjack = FlapJack::Directive(Directive::Destroy {
+            wallet: wallet_name.to_owned(),
+            comment: None,
         });
-        self.push_flap(flapjack);
+        self.insert_flap(flapjack, None)
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FlapJackDb {
     // each type of transaction will have a vector of transactions in order
     pub wallet_amounts: HashMap<String, f64>,
+    /// The overdraft policy of each wallet that has one.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub policies: HashMap<String, WalletPolicy>,
+    /// The details of each wallet that has any, sorted by key.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub metadata: HashMap<String, BTreeMap<String, String>>,
 }
 
 impl FlapJackDb {
     pub fn from_flaps(flaps: &Vec<FlapJack>) -> Self {
         let mut db = Self {
             wallet_amounts: HashMap::new(),
+            policies: HashMap::new(),
+            metadata: HashMap::new(),
         };
 
         for flapjack in flaps {
-            match flapjack {
-                FlapJack::Comment(_comment) => {}
-                FlapJack::Directive(directive) => {
-                    let command = &directive.command;
-                    let params = directive.params.as_slice();
-
-                    match command {
-                        Command::Create => db.command_create(params),
-                        Command::Increment => db.command_increment(params),
-                        Command::Set => db.command_set(params),
-                        Command::Destroy => db.command_destroy(params),
-                        Command::Decrement => db.command_decrement(params),
-                    }
-                }
-            }
+            db.update(flapjack);
         }
 
         db
     }
 
+    pub fn wallet_names(&self) -> Vec<String> {
+        let mut names = Vec::new();
+
+        for wallet_name in self.wallet_amounts.keys() {
+            names.push(wallet_name.to_owned());
+        }
+
+        // Sort the names alphabetically
+        names.sort();
+
+        names
+    }
+
     // takes a flapjack, updates the db
     pub fn update(&mut self, flap: &FlapJack) {
         match flap {
             FlapJack::Comment(_comment) => {}
-            FlapJack::Directive(directive) => {
-                let command = &directive.command;
-                let params = directive.params.as_slice();
-
-                match command {
-                    Command::Create => self.command_create(params),
-                    Command::Increment => self.command_increment(params),
-                    Command::Set => self.command_set(params),
-                    Command::Destroy => self.command_destroy(params),
-                    Command::Decrement => self.command_decrement(params),
+            FlapJack::Blank => {}
+            FlapJack::Directive(directive) => match directive {
+                Directive::Create {
+                    wallet,
+                    policy,
+                    meta,
+                    ..
+                } => {
+                    self.command_create(wallet);
+                    match policy {
+                        Some(policy) => self.policies.insert(wallet.clone(), policy.clone()),
+                        None => self.policies.remove(wallet),
+                    };
+                    self.metadata.remove(wallet);
+                    self.command_meta(wallet, meta);
                 }
-            }
+                Directive::Increment { wallet, amount, .. } => {
+                    self.command_increment(wallet, amount.value())
+                }
+                Directive::Set { wallet, amount, .. } => self.command_set(wallet, amount.value()),
+                Directive::Destroy { wallet, .. } => {
+                    self.command_destroy(wallet);
+                    self.policies.remove(wallet);
+                    self.metadata.remove(wallet);
+                }
+                Directive::Decrement { wallet, amount, .. } => {
+                    self.command_decrement(wallet, amount.value())
+                }
+                // the lines of an included file follow its INCLUDE directive
+                Directive::Include { .. } => {}
+                // the wallets a checkpoint starts with are set by the directives after it
+                Directive::Checkpoint { .. } => {}
+                Directive::Chain => {}
+                // only the directives a template adds change any balances
+                Directive::Recur { .. } => {}
+                // budgets only limit spending, they do not change balances
+                Directive::Budget { .. } => {}
+                Directive::Goal { .. } => {}
+                Directive::Policy { wallet, policy } => {
+                    self.policies.insert(wallet.clone(), policy.clone());
+                }
+                Directive::Meta { wallet, meta } => self.command_meta(wallet, meta),
+                // directives from other versions or extensions do not change any balances
+                Directive::Other { .. } => {}
+            },
         }
     }
 
     // TODO: make sure wallet doesnt already exist
-    pub fn command_create(&mut self, params: &[String]) {
-        let wallet_type = params.get(0).expect("Wallet type argument was not found!");
-
+    pub fn command_create(&mut self, wallet_type: &str) {
         self.wallet_amounts.insert(wallet_type.to_string(), 0.0);
     }
 
-    pub fn command_increment(&mut self, params: &[String]) {
-        let wallet_type = params.get(0).expect("Wallet type argument was not found!");
-
-        let amount = params
-            .get(1)
-            .expect("Amount was not found")
-            .parse::<f64>()
-            .expect("Amount could not be parsed to a float.");
-
+    pub fn command_increment(&mut self, wallet_type: &str, amount: f64) {
         match self.wallet_amounts.get_mut(wallet_type) {
             Some(wallet_balance) => {
                 *wallet_balance += amount;
@@ -230,15 +457,7 @@ impl FlapJackDb {
         };
     }
 
-    pub fn command_set(&mut self, params: &[String]) {
-        let wallet_type = params.get(0).expect("Wallet type argument was not found!");
-
-        let amount = params
-            .get(1)
-            .expect("Amount was not found")
-            .parse::<f64>()
-            .expect("Amount could not be parsed to a float.");
-
+    pub fn command_set(&mut self, wallet_type: &str, amount: f64) {
         match self.wallet_amounts.get_mut(wallet_type) {
             Some(wallet_balance) => {
                 *wallet_balance = amount;
@@ -249,20 +468,26 @@ impl FlapJackDb {
         };
     }
 
-    pub fn command_destroy(&mut self, params: &[String]) {
-        let wallet_type = params.get(0).expect("Wallet type argument was not found!");
-        self.wallet_amounts.remove(wallet_type);
-    }
+    pub fn command_meta(&mut self, wallet_type: &str, meta: &[(String, String)]) {
+        let details = self.metadata.entry(wallet_type.to_owned()).or_default();
+        for (key, value) in meta {
+            if value.is_empty() {
+                details.remove(key);
+            } else {
+                details.insert(key.clone(), value.clone());
+            }
+        }
 
-    pub fn command_decrement(&mut self, params: &[String]) {
-        let wallet_type = params.get(0).expect("Wallet type argument was not found!");
+        if details.is_empty() {
+            self.metadata.remove(wallet_type);
+        }
+    }
 
-        let amount = params
-            .get(1)
-            .expect("Amount was not found")
-            .parse::<f64>()
-            .expect("Amount could not be parsed to a float.");
+    pub fn command_destroy(&mut self, wallet_type: &str) {
+        self.wallet_amounts.remove(wallet_type);
+    }
 
+    pub fn command_decrement(&mut self, wallet_type: &str, amount: f64) {
         match self.wallet_amounts.get_mut(wallet_type) {
             Some(wallet_balance) => {
                 *wallet_balance -= amount;
@@ -276,6 +501,7 @@ impl FlapJackDb {
 
 #[cfg(test)]
 mod tests {
+    use crate::flapjack_stack::flapjack::{Amount, Directive, FlapJack};
     use crate::flapjack_stack::flapjack_stack_builder::FlapJackStackBuilder;
 
     #[test]
@@ -286,23 +512,55 @@ mod tests {
         INCREMENT \"Checking (Bank)\" 50 \"this is a comment for this transactions\"
         ";
 
-        let seq = FlapJackStackBuilder::new(log, None).build();
+        let seq = FlapJackStackBuilder::new(log, None).build().unwrap();
         let serialized_first = seq.serialize();
 
-        let seq_rebuilt = FlapJackStackBuilder::new(&serialized_first, None).build();
+        let seq_rebuilt = FlapJackStackBuilder::new(&serialized_first, None)
+            .build()
+            .unwrap();
         let serialized_again = seq_rebuilt.serialize();
 
         assert_eq!(serialized_first, serialized_again)
     }
 
+    #[test]
+    fn test_lossless_round_trip() {
+        let log =
+            "# balances\r\n\r\n  CREATE   Cash\r\nINCREMENT Cash 5.00  # from the jar\r\n\r\n";
+        let seq = FlapJackStackBuilder::new(log, None).build().unwrap();
+
+        assert_eq!(seq.flapjacks[1], FlapJack::Blank);
+        assert_eq!(seq.trailing_comment(3), Some("# from the jar".to_owned()));
+        assert_eq!(seq.db.wallet_amounts.get("Cash"), Some(&5.0));
+        assert_eq!(seq.serialize(), log);
+    }
+
+    #[test]
+    fn test_replaced_flap_is_rendered() {
+        let log = "CREATE   Cash\nSET   Cash   5.00\n";
+        let mut seq = FlapJackStackBuilder::new(log, None).build().unwrap();
+
+        seq.replace_flap(
+            1,
+            FlapJack::Directive(Directive::Set {
+                wallet: "Cash".to_owned(),
+                amount: Amount::new(7.0),
+                comment: None,
+            }),
+        );
+
+        assert_eq!(seq.serialize(), "CREATE   Cash\nSET Cash 7\n");
+        assert_eq!(seq.db.wallet_amounts.get("Cash"), Some(&7.0));
+    }
+
     #[test]
     fn test_db_wallet_creation() {
         let log = "# the program will register this line a comment\nCREATE \"Checking (Bank)\"\nCREATE \"Savings (Bank)\"";
-        let seq = FlapJackStackBuilder::new(log, None).build();
+        let seq = FlapJackStackBuilder::new(log, None).build().unwrap();
 
         seq.db
             .wallet_amounts
-            .get(&"Checking (Bank)".to_owned())
+            .get("Checking (Bank)")
             .expect("This key does not exist!");
     }
 
@@ -316,7 +574,7 @@ mod tests {
         INCREMENT \"Checking (Bank)\" 25.50 \"this is another comment for the transaction\"
         ";
 
-        let seq = FlapJackStackBuilder::new(log, None).build();
+        let seq = FlapJackStackBuilder::new(log, None).build().unwrap();
         match seq.db.wallet_amounts.get("Checking (Bank)") {
             Some(balance) => {
                 assert_eq!(*balance, 75.5);
@@ -347,7 +605,7 @@ mod tests {
         SET \"Savings (Bank)\" 200 \"meow\"
         ";
 
-        let seq = FlapJackStackBuilder::new(log, None).build();
+        let seq = FlapJackStackBuilder::new(log, None).build().unwrap();
         match seq.db.wallet_amounts.get("Checking (Bank)") {
             Some(balance) => {
                 assert_eq!(*balance, 75.5);
@@ -379,9 +637,9 @@ mod tests {
         DESTROY \"Savings (Bank)\"
         ";
 
-        let seq = FlapJackStackBuilder::new(log, None).build();
+        let seq = FlapJackStackBuilder::new(log, None).build().unwrap();
 
-        if seq.db.wallet_amounts.get("Savings (Bank)").is_some() {
+        if seq.db.wallet_amounts.contains_key("Savings (Bank)") {
             panic!("Wallet was not destroyed!")
         }
     }
@@ -398,7 +656,7 @@ mod tests {
         DECREMENT \"Checking (Bank)\" 10.5 \"bought something\"
         ";
 
-        let seq = FlapJackStackBuilder::new(log, None).build();
+        let seq = FlapJackStackBuilder::new(log, None).build().unwrap();
         match seq.db.wallet_amounts.get("Checking (Bank)") {
             Some(balance) => {
                 assert_eq!(*balance, 65.0);
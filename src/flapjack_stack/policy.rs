@@ -0,0 +1,200 @@
+//! What a wallet does when a directive would take its balance below zero.
+
+use std::fmt;
+
+use super::flapjack::{Amount, Directive, FlapJack};
+use super::FlapJackDb;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Policy {
+    /// The balance can go as low as it likes. Wallets without a policy allow it.
+    Allow,
+    /// Going past the limit is allowed, but asks first.
+    Warn,
+    /// Going past the limit is refused.
+    Forbid,
+}
+
+impl Policy {
+    pub fn parse(text: &str) -> Option<Self> {
+        match text {
+            "allow" => Some(Self::Allow),
+            "warn" => Some(Self::Warn),
+            "forbid" => Some(Self::Forbid),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Policy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Allow => write!(f, "allow"),
+            Self::Warn => write!(f, "warn"),
+            Self::Forbid => write!(f, "forbid"),
+        }
+    }
+}
+
+/// The policy of a wallet and how far below zero it can go before the policy applies.
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WalletPolicy {
+    pub policy: Policy,
+    /// A credit limit. Without one the policy applies below zero.
+    pub limit: Option<Amount>,
+}
+
+impl WalletPolicy {
+    /// The lowest balance the policy allows without a warning.
+    pub fn floor(&self) -> f64 {
+        self.limit.as_ref().map(|x| -x.value()).unwrap_or(0.0)
+    }
+}
+
+/// A directive that would take a wallet past the limit of its policy.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Overdraft {
+    pub wallet: String,
+    pub balance: f64,
+    pub floor: f64,
+    pub policy: Policy,
+}
+
+impl fmt::Display for Overdraft {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} would go to {:.2}, past its limit of {:.2}.",
+            self.wallet, self.balance, self.floor
+        )?;
+        match self.policy {
+            Policy::Forbid => write!(f, " Its policy forbids going that low."),
+            Policy::Warn => write!(f, " Its policy warns before going that low."),
+            Policy::Allow => Ok(()),
+        }
+    }
+}
+
+impl std::error::Error for Overdraft {}
+
+impl FlapJackDb {
+    /// Returns the overdraft if moving the wallet to `balance` goes against its policy.
+    /// A balance that is already past the limit can still be brought back up.
+    pub fn check_balance(&self, wallet: &str, balance: f64) -> Option<Overdraft> {
+        let policy = self.policies.get(wallet)?;
+        let current = self.wallet_amounts.get(wallet).copied().unwrap_or_default();
+        if policy.policy == Policy::Allow || balance >= policy.floor() || balance >= current {
+            return None;
+        }
+
+        Some(Overdraft {
+            wallet: wallet.to_owned(),
+            balance,
+            floor: policy.floor(),
+            policy: policy.policy,
+        })
+    }
+
+    /// Returns the overdraft if the flap would go against the policy of its wallet.
+    pub fn check_policy(&self, flap: &FlapJack) -> Option<Overdraft> {
+        let (wallet, balance) = match flap {
+            FlapJack::Directive(Directive::Increment { wallet, amount, .. }) => {
+                (wallet, self.wallet_amounts.get(wallet)? + amount.value())
+            }
+            FlapJack::Directive(Directive::Decrement { wallet, amount, .. }) => {
+                (wallet, self.wallet_amounts.get(wallet)? - amount.value())
+            }
+            FlapJack::Directive(Directive::Set { wallet, amount, .. }) => (wallet, amount.value()),
+            _ => return None,
+        };
+
+        self.check_balance(wallet, balance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Policy, WalletPolicy};
+    use crate::flapjack_stack::flapjack::{Amount, Directive};
+    use crate::flapjack_stack::flapjack_stack_builder::FlapJackStackBuilder;
+    use crate::flapjack_stack::PushError;
+
+    #[test]
+    fn test_check_balance() {
+        let log = r#"CREATE Cash policy=forbid
+CREATE Card "visa" policy=warn limit=500
+CREATE Loose
+INCREMENT Cash 20"#;
+        let stack = FlapJackStackBuilder::new(log, None).build().unwrap();
+
+        assert_eq!(stack.db.check_balance("Cash", 0.0), None);
+        assert_eq!(
+            stack.db.check_balance("Cash", -5.0).map(|x| x.policy),
+            Some(Policy::Forbid)
+        );
+        assert_eq!(stack.db.check_balance("Card", -500.0), None);
+        assert_eq!(
+            stack.db.check_balance("Card", -501.0).map(|x| x.floor),
+            Some(-500.0)
+        );
+        assert_eq!(stack.db.check_balance("Loose", -1000.0), None);
+
+        assert_eq!(
+            stack.db.check_balance("Cash", -5.0).unwrap().to_string(),
+            "Cash would go to -5.00, past its limit of 0.00. Its policy forbids going that low."
+        );
+        assert_eq!(
+            stack.db.check_balance("Card", -501.0).unwrap().to_string(),
+            "Card would go to -501.00, past its limit of -500.00. Its policy warns before going that low."
+        );
+    }
+
+    #[test]
+    fn test_policy_directive_replaces_policy() {
+        let log = r#"CREATE Cash policy=forbid
+POLICY Cash allow
+CREATE Card
+POLICY Card forbid 100
+SET Card -150"#;
+        let stack = FlapJackStackBuilder::new(log, None).build().unwrap();
+
+        assert_eq!(stack.db.check_balance("Cash", -5.0), None);
+        // a wallet that is already past its limit can still be paid back
+        assert_eq!(stack.db.check_balance("Card", -120.0), None);
+        assert!(stack.db.check_balance("Card", -160.0).is_some());
+    }
+
+    #[test]
+    fn test_forbidden_push_writes_nothing() {
+        let log = "CREATE Cash policy=forbid\nINCREMENT Cash 20\n";
+        let mut stack = FlapJackStackBuilder::new(log, None).build().unwrap();
+
+        let pushed = stack.decrement_wallet_amount("Cash", 25.0, None);
+        assert!(matches!(pushed, Err(PushError::Overdraft(x)) if x.balance == -5.0));
+        assert_eq!(stack.serialize(), log);
+        assert_eq!(stack.amount("Cash"), 20.0);
+
+        stack.decrement_wallet_amount("Cash", 20.0, None).unwrap();
+        assert_eq!(stack.amount("Cash"), 0.0);
+    }
+
+    #[test]
+    fn test_policy_params_round_trip() {
+        let create = Directive::Create {
+            wallet: "Credit Card".to_owned(),
+            comment: None,
+            policy: Some(WalletPolicy {
+                policy: Policy::Warn,
+                limit: Some(Amount::parse("1500").unwrap()),
+            }),
+            meta: Vec::new(),
+        };
+        assert_eq!(
+            create.serialize(),
+            r#"CREATE "Credit Card" policy=warn limit=1500"#
+        );
+        assert_eq!(Directive::parse("CREATE", create.params(), 1), Ok(create));
+    }
+}
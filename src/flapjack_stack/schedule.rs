@@ -0,0 +1,457 @@
+//! Directives that repeat on a schedule.
+//!
+//! A `RECUR` directive is a template. Each occurrence that is due is added to the log as a real
+//! directive, with the date at the start of its comment and a `# recur:<id>` trailing comment that
+//! links it back to its template. The latest linked occurrence is how flapjack knows where the
+//! schedule left off.
+
+use std::fmt;
+
+use chrono::{Datelike, Days, Months, NaiveDate};
+
+use super::chain::link;
+use super::flapjack::{Directive, FlapJack};
+use super::policy::{Overdraft, Policy};
+use super::{FlapJackStack, PushError};
+use crate::import::{split_date, DATE_FORMAT};
+
+pub const RECUR_TAG: &str = "recur:";
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Frequency {
+    Weekly,
+    Monthly,
+}
+
+impl Frequency {
+    pub fn parse(text: &str) -> Option<Self> {
+        match text {
+            "weekly" => Some(Self::Weekly),
+            "monthly" => Some(Self::Monthly),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Frequency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Weekly => write!(f, "weekly"),
+            Self::Monthly => write!(f, "monthly"),
+        }
+    }
+}
+
+/// When a `RECUR` template is due.
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Schedule {
+    pub frequency: Frequency,
+    /// The day of the week from 1 (Monday) to 7 for weekly schedules, and the day of the month
+    /// from 1 to 31 for monthly ones. Months without that day use their last day.
+    pub day: u32,
+}
+
+impl Schedule {
+    /// Returns None if the day does not fit the frequency.
+    pub fn new(frequency: Frequency, day: u32) -> Option<Self> {
+        let last_day = match frequency {
+            Frequency::Weekly => 7,
+            Frequency::Monthly => 31,
+        };
+        (1..=last_day)
+            .contains(&day)
+            .then_some(Self { frequency, day })
+    }
+
+    /// The last day the schedule is due on, up to and including `date`.
+    pub fn latest_on_or_before(&self, date: NaiveDate) -> NaiveDate {
+        match self.frequency {
+            Frequency::Weekly => {
+                let days_since = (date.weekday().number_from_monday() + 7 - self.day) % 7;
+                date - Days::new(days_since.into())
+            }
+            Frequency::Monthly => {
+                let this_month = self.day_in_month(date);
+                if this_month <= date {
+                    return this_month;
+                }
+                self.day_in_month(date - Months::new(1))
+            }
+        }
+    }
+
+    // the scheduled day in the month of `date`
+    fn day_in_month(&self, date: NaiveDate) -> NaiveDate {
+        (1..=self.day)
+            .rev()
+            .find_map(|day| date.with_day(day))
+            .expect("Every month has a first day")
+    }
+}
+
+/// A directive that a template is due to add.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Occurrence {
+    pub date: NaiveDate,
+    pub flapjack: FlapJack,
+    /// Links the directive to its template, as `recur:<id>`.
+    pub note: String,
+}
+
+/// What running the schedule did.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct ScheduleRun {
+    /// The occurrences that were added to the log, oldest first.
+    pub added: Vec<Occurrence>,
+    /// The occurrences that were skipped because they would overdraw a wallet that forbids it.
+    pub blocked: Vec<(Occurrence, Overdraft)>,
+    /// The added occurrences that took a wallet that only warns past its limit.
+    pub warnings: Vec<(Occurrence, Overdraft)>,
+}
+
+/// Identifies a template by its `id=` name if it has one, and otherwise by its schedule and
+/// everything it adds, with the amount by value so `5` and `5.00` are the same template.
+pub fn template_id(schedule: &Schedule, id: Option<&str>, template: &Directive) -> String {
+    if let Some(id) = id {
+        return link("", &format!("id={}", id));
+    }
+    link(
+        "",
+        &format!(
+            "{} {} {} {} {} {}",
+            schedule.frequency,
+            schedule.day,
+            template.keyword(),
+            template.wallet().unwrap_or_default(),
+            template.amount().map(|x| x.value()).unwrap_or_default(),
+            template.comment().unwrap_or_default()
+        ),
+    )
+}
+
+impl FlapJackStack {
+    /// Every occurrence that is due on or before `today` and not in the log yet, oldest first.
+    /// A template that has never been run only adds its latest occurrence, and templates for
+    /// wallets that no longer exist add nothing.
+    pub fn due_occurrences(&self, today: NaiveDate) -> Vec<Occurrence> {
+        let mut due = Vec::new();
+
+        for flapjack in &self.flapjacks {
+            let (schedule, id, template) = match flapjack {
+                FlapJack::Directive(Directive::Recur {
+                    schedule,
+                    id,
+                    template,
+                }) => (schedule, id.as_deref(), template),
+                _ => continue,
+            };
+            let wallet = template.wallet().unwrap_or_default();
+            if !self.db.wallet_amounts.contains_key(wallet) {
+                continue;
+            }
+
+            let note = format!("{}{}", RECUR_TAG, template_id(schedule, id, template));
+            let last_run = self.last_occurrence(&note);
+
+            let mut date = schedule.latest_on_or_before(today);
+            let mut dates = Vec::new();
+            while last_run.is_none_or(|x| date > x) {
+                dates.push(date);
+                if last_run.is_none() {
+                    break;
+                }
+                date = schedule.latest_on_or_before(date - Days::new(1));
+            }
+
+            for date in dates.into_iter().rev() {
+                due.push(Occurrence {
+                    date,
+                    flapjack: FlapJack::Directive(with_date(template, date)),
+                    note: note.clone(),
+                });
+            }
+        }
+
+        // stable, so templates due on the same day keep the order of the log
+        due.sort_by_key(|x| x.date);
+        due
+    }
+
+    /// Adds every due occurrence to the log. Occurrences that would overdraw a wallet that
+    /// forbids it are skipped, and the others are checked as if the skipped ones were not there
+    /// before anything is written. A skipped occurrence is due again the next time the schedule
+    /// runs, unless a later occurrence of its template was added. Occurrences that take a wallet
+    /// that only warns past its limit are added, and listed in the warnings.
+    pub fn run_schedule(&mut self, today: NaiveDate) -> Result<ScheduleRun, PushError> {
+        let mut run = ScheduleRun::default();
+        let mut db = self.db.clone();
+        for occurrence in self.due_occurrences(today) {
+            match db.check_policy(&occurrence.flapjack) {
+                Some(overdraft) if overdraft.policy == Policy::Forbid => {
+                    run.blocked.push((occurrence, overdraft));
+                    continue;
+                }
+                Some(overdraft) => run.warnings.push((occurrence.clone(), overdraft)),
+                None => {}
+            }
+            db.update(&occurrence.flapjack);
+            run.added.push(occurrence);
+        }
+
+        for occurrence in &run.added {
+            self.push_flap_with_note(occurrence.flapjack.clone(), &occurrence.note)?;
+        }
+        Ok(run)
+    }
+
+    /// The RECUR templates of wallets that are still open, and the latest occurrence of each,
+    /// which is where its schedule left off. A checkpoint keeps these lines.
+    pub(super) fn schedule_lines(&self) -> Vec<usize> {
+        let mut lines = Vec::new();
+        for (i, flapjack) in self.flapjacks.iter().enumerate() {
+            let (schedule, id, template) = match flapjack {
+                FlapJack::Directive(Directive::Recur {
+                    schedule,
+                    id,
+                    template,
+                }) => (schedule, id.as_deref(), template),
+                _ => continue,
+            };
+            if !self
+                .db
+                .wallet_amounts
+                .contains_key(template.wallet().unwrap_or_default())
+            {
+                continue;
+            }
+
+            lines.push(i);
+            let note = format!("{}{}", RECUR_TAG, template_id(schedule, id, template));
+            lines.extend(self.latest_occurrence(&note).map(|(x, _)| x));
+        }
+        lines
+    }
+
+    // the date of the latest directive linked with `note`
+    fn last_occurrence(&self, note: &str) -> Option<NaiveDate> {
+        self.latest_occurrence(note).map(|(_, date)| date)
+    }
+
+    // the index and date of the latest directive linked with `note`
+    fn latest_occurrence(&self, note: &str) -> Option<(usize, NaiveDate)> {
+        (0..self.flapjacks.len())
+            .filter(|i| {
+                self.trailing_comment(*i)
+                    .is_some_and(|x| x.split_whitespace().any(|word| word == note))
+            })
+            .filter_map(|i| match &self.flapjacks[i] {
+                FlapJack::Directive(directive) => Some((i, split_date(directive.comment()?).0?)),
+                _ => None,
+            })
+            .max_by_key(|(_, date)| *date)
+    }
+}
+
+/// The directive with the date at the start of its comment.
+pub(super) fn with_date(template: &Directive, date: NaiveDate) -> Directive {
+    let date = date.format(DATE_FORMAT).to_string();
+    let dated = |comment: &Option<String>| match comment {
+        Some(comment) => Some(format!("{} {}", date, comment)),
+        None => Some(date.clone()),
+    };
+
+    match template {
+        Directive::Increment {
+            wallet,
+            amount,
+            comment,
+        } => Directive::Increment {
+            wallet: wallet.clone(),
+            amount: amount.clone(),
+            comment: dated(comment),
+        },
+        Directive::Decrement {
+            wallet,
+            amount,
+            comment,
+        } => Directive::Decrement {
+            wallet: wallet.clone(),
+            amount: amount.clone(),
+            comment: dated(comment),
+        },
+        Directive::Set {
+            wallet,
+            amount,
+            comment,
+        } => Directive::Set {
+            wallet: wallet.clone(),
+            amount: amount.clone(),
+            comment: dated(comment),
+        },
+        // the parser only accepts the directives above as templates
+        other => other.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Frequency, Schedule};
+    use crate::flapjack_stack::flapjack::ParseError;
+    use crate::flapjack_stack::flapjack_stack_builder::FlapJackStackBuilder;
+    use chrono::NaiveDate;
+
+    fn date(text: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(text, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_latest_on_or_before() {
+        let monthly = Schedule::new(Frequency::Monthly, 31).unwrap();
+        assert_eq!(
+            monthly.latest_on_or_before(date("2026-03-15")),
+            date("2026-02-28")
+        );
+        assert_eq!(
+            monthly.latest_on_or_before(date("2026-03-31")),
+            date("2026-03-31")
+        );
+
+        // 2026-10-19 is a Monday
+        let weekly = Schedule::new(Frequency::Weekly, 5).unwrap();
+        assert_eq!(
+            weekly.latest_on_or_before(date("2026-10-19")),
+            date("2026-10-16")
+        );
+        assert_eq!(Schedule::new(Frequency::Weekly, 8), None);
+    }
+
+    #[test]
+    fn test_run_schedule() {
+        let log = "CREATE Checking\nRECUR monthly 1 DECREMENT Checking 1200 rent\n";
+        let mut stack = FlapJackStackBuilder::new(log, None).build().unwrap();
+
+        // the first run only adds the latest occurrence
+        let added = stack.run_schedule(date("2026-08-20")).unwrap().added;
+        assert_eq!(added.len(), 1);
+        assert!(added[0].note.starts_with("recur:"));
+        assert!(stack.serialize().ends_with(&format!(
+            "DECREMENT Checking 1200 \"2026-08-01 rent\" # {}\n",
+            added[0].note
+        )));
+
+        // later runs catch up on every month since
+        let added = stack.run_schedule(date("2026-10-19")).unwrap().added;
+        assert_eq!(
+            added.iter().map(|x| x.date).collect::<Vec<_>>(),
+            vec![date("2026-09-01"), date("2026-10-01")]
+        );
+        assert_eq!(stack.db.wallet_amounts.get("Checking"), Some(&-3600.0));
+        assert!(stack
+            .run_schedule(date("2026-10-19"))
+            .unwrap()
+            .added
+            .is_empty());
+    }
+
+    #[test]
+    fn test_blocked_occurrences_are_skipped() {
+        let log = "CREATE Checking policy=forbid\nINCREMENT Checking 2900\n\
+                   RECUR monthly 1 DECREMENT Checking 1200 rent\n\
+                   RECUR monthly 2 DECREMENT Checking 200 phone\n";
+        let mut stack = FlapJackStackBuilder::new(log, None).build().unwrap();
+        stack.run_schedule(date("2026-08-20")).unwrap();
+
+        // September fits, but October would go past zero
+        let run = stack.run_schedule(date("2026-10-19")).unwrap();
+        let added = run
+            .added
+            .iter()
+            .map(|x| x.flapjack.serialize())
+            .collect::<Vec<String>>();
+        assert_eq!(
+            added,
+            vec![
+                "DECREMENT Checking 1200 \"2026-09-01 rent\"",
+                "DECREMENT Checking 200 \"2026-09-02 phone\"",
+            ]
+        );
+        assert_eq!(run.blocked.len(), 2);
+        assert_eq!(run.blocked[0].0.date, date("2026-10-01"));
+        assert_eq!(stack.amount("Checking"), 100.0);
+    }
+
+    #[test]
+    fn test_warned_occurrences_are_added_with_a_warning() {
+        let log = "CREATE Checking policy=warn limit=100\n\
+                   RECUR monthly 1 DECREMENT Checking 1200 rent\n";
+        let mut stack = FlapJackStackBuilder::new(log, None).build().unwrap();
+
+        let run = stack.run_schedule(date("2026-10-19")).unwrap();
+        assert_eq!(run.added.len(), 1);
+        assert!(run.blocked.is_empty());
+        assert_eq!(run.warnings.len(), 1);
+        assert_eq!(run.warnings[0].1.balance, -1200.0);
+        assert_eq!(stack.amount("Checking"), -1200.0);
+    }
+
+    #[test]
+    fn test_template_id() {
+        let note = |recur: &str| {
+            let log = format!("CREATE Checking\n{}\n", recur);
+            let mut stack = FlapJackStackBuilder::new(&log, None).build().unwrap();
+            assert_eq!(stack.serialize(), log);
+            stack.run_schedule(date("2026-10-19")).unwrap().added[0]
+                .note
+                .clone()
+        };
+
+        let rent = note("RECUR monthly 1 DECREMENT Checking 1200 rent");
+        assert_eq!(
+            rent,
+            note("RECUR monthly 1 DECREMENT Checking 1200.00 rent")
+        );
+        assert_ne!(rent, note("RECUR monthly 1 DECREMENT Checking 1300 rent"));
+
+        // a named template keeps its link through any edit
+        let named = note("RECUR monthly 1 id=rent DECREMENT Checking 1200 rent");
+        assert_ne!(named, rent);
+        assert_eq!(
+            named,
+            note("RECUR monthly 1 id=rent DECREMENT Checking 1300 \"rent and parking\"")
+        );
+    }
+
+    #[test]
+    fn test_recur_errors() {
+        let build = |log: &str| FlapJackStackBuilder::new(log, None).build().unwrap_err();
+
+        assert_eq!(
+            build("RECUR yearly 1 SET Cash 0"),
+            ParseError::InvalidSchedule {
+                line: 1,
+                schedule: "yearly 1".to_owned()
+            }
+        );
+        assert_eq!(
+            build("RECUR monthly 1 CREATE Cash"),
+            ParseError::InvalidRecurCommand {
+                line: 1,
+                command: "CREATE".to_owned()
+            }
+        );
+        assert_eq!(
+            build("RECUR monthly 1"),
+            ParseError::MissingSchedule { line: 1 }
+        );
+        assert_eq!(
+            build("RECUR monthly 1 id= SET Cash 0"),
+            ParseError::MissingRecurId { line: 1 }
+        );
+        assert_eq!(
+            build("RECUR monthly 1 id=rent"),
+            ParseError::MissingSchedule { line: 1 }
+        );
+    }
+}
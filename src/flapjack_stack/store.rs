@@ -0,0 +1,235 @@
+//! Where the files of a `FlapJackStack` are read from and written to.
+
+use std::fmt;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::encryption::EncryptionError;
+
+#[derive(thiserror::Error, Debug)]
+pub enum StoreError {
+    #[error("Could not read the log. ({0})")]
+    CouldNotRead(#[source] io::Error),
+    #[error(transparent)]
+    Encryption(#[from] EncryptionError),
+}
+
+/// The storage behind one file of the log.
+/// The stack appends to it when a flapjack is pushed to the end of the file, and rewrites it otherwise.
+pub trait LogStore: fmt::Debug {
+    /// Reads everything in the store.
+    fn load(&self) -> io::Result<Vec<u8>>;
+
+    /// Adds bytes to the end of the store.
+    fn append(&mut self, bytes: &[u8]) -> io::Result<()>;
+
+    /// Replaces everything in the store.
+    fn rewrite(&mut self, bytes: &[u8]) -> io::Result<()>;
+
+    /// The path of the file behind the store, which `INCLUDE` paths are relative to.
+    fn path(&self) -> Option<&str> {
+        None
+    }
+}
+
+/// A file on disk.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileStore {
+    path: String,
+}
+
+impl FileStore {
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: path.to_owned(),
+        }
+    }
+}
+
+impl LogStore for FileStore {
+    fn load(&self) -> io::Result<Vec<u8>> {
+        fs::read(&self.path)
+    }
+
+    fn append(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(bytes)
+    }
+
+    // the new contents are written next to the file and then renamed over it,
+    // so a crash part way through leaves the old file as it was
+    fn rewrite(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let path = Path::new(&self.path);
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        let temp_path = path.with_file_name(format!(".{}.tmp", file_name));
+
+        // a read-only file stays that way, and a new file keeps the permissions of the old one
+        let permissions = match fs::metadata(path) {
+            Ok(metadata) if metadata.permissions().readonly() => {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    "the file is read-only",
+                ))
+            }
+            Ok(metadata) => Some(metadata.permissions()),
+            Err(_) => None,
+        };
+
+        let written = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&temp_path)
+            .and_then(|mut file| {
+                file.write_all(bytes)?;
+                if let Some(permissions) = permissions {
+                    file.set_permissions(permissions)?;
+                }
+                file.sync_all()
+            })
+            .and_then(|_| fs::rename(&temp_path, path));
+
+        if written.is_err() {
+            let _ = fs::remove_file(&temp_path);
+        }
+        written
+    }
+
+    fn path(&self) -> Option<&str> {
+        Some(&self.path)
+    }
+}
+
+/// A log kept in memory, for tests and for embedding flapjack without a file.
+/// Clones share their contents, so a clone can be kept to read what the stack wrote.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStore {
+    contents: Arc<Mutex<Vec<u8>>>,
+}
+
+impl MemoryStore {
+    pub fn new(contents: &[u8]) -> Self {
+        Self {
+            contents: Arc::new(Mutex::new(contents.to_vec())),
+        }
+    }
+
+    pub fn contents(&self) -> Vec<u8> {
+        self.contents.lock().unwrap().clone()
+    }
+}
+
+impl LogStore for MemoryStore {
+    fn load(&self) -> io::Result<Vec<u8>> {
+        Ok(self.contents())
+    }
+
+    fn append(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.contents.lock().unwrap().extend_from_slice(bytes);
+        Ok(())
+    }
+
+    fn rewrite(&mut self, bytes: &[u8]) -> io::Result<()> {
+        *self.contents.lock().unwrap() = bytes.to_vec();
+        Ok(())
+    }
+}
+
+#[cfg(feature = "serde")]
+pub(super) fn default_store() -> Box<dyn LogStore> {
+    Box::new(MemoryStore::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FileStore, LogStore, MemoryStore};
+    use crate::encryption::{self, EncryptionKey};
+    use crate::flapjack_stack::flapjack_stack_builder::FlapJackStackBuilder;
+    use crate::flapjack_stack::PushError;
+    use std::env;
+    use std::fs;
+    use std::io;
+
+    // a store that can be read but not written, like a file on a full disk
+    #[derive(Debug)]
+    struct ReadOnlyStore(Vec<u8>);
+
+    impl LogStore for ReadOnlyStore {
+        fn load(&self) -> io::Result<Vec<u8>> {
+            Ok(self.0.clone())
+        }
+
+        fn append(&mut self, _: &[u8]) -> io::Result<()> {
+            Err(io::Error::other("read only"))
+        }
+
+        fn rewrite(&mut self, _: &[u8]) -> io::Result<()> {
+            Err(io::Error::other("read only"))
+        }
+    }
+
+    #[test]
+    fn test_rewrite_replaces_the_file() {
+        let path = env::temp_dir().join("flapjack_rewrite.flap");
+        fs::write(&path, "CREATE Cash\nINCREMENT Cash 5\n").unwrap();
+
+        let mut store = FileStore::new(&path.to_string_lossy());
+        store.rewrite(b"CREATE Cash\n").unwrap();
+
+        assert_eq!(fs::read(&path).unwrap(), b"CREATE Cash\n");
+        // the temporary file is renamed over the log, so it is gone
+        assert!(!env::temp_dir().join(".flapjack_rewrite.flap.tmp").exists());
+    }
+
+    #[test]
+    fn test_failed_write_keeps_nothing() {
+        let store = ReadOnlyStore(b"CREATE Cash\n".to_vec());
+        let mut stack = FlapJackStackBuilder::from_store(store, None)
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let pushed = stack.increment_wallet_amount("Cash", 5.0, None);
+        assert!(matches!(pushed, Err(PushError::Write(_))));
+        assert!(stack.destroy_wallet("Cash").is_err());
+
+        assert_eq!(stack.serialize(), "CREATE Cash\n");
+        assert_eq!(stack.amount("Cash"), 0.0);
+    }
+
+    #[test]
+    fn test_log_without_path_is_kept_in_memory() {
+        let mut stack = FlapJackStackBuilder::new("CREATE Cash\n", None)
+            .build()
+            .unwrap();
+        stack.increment_wallet_amount("Cash", 5.0, None).unwrap();
+
+        assert_eq!(
+            stack.files[0].store.load().unwrap(),
+            b"CREATE Cash\nINCREMENT Cash 5\n"
+        );
+    }
+
+    #[test]
+    fn test_pushed_flaps_are_appended() {
+        let store = MemoryStore::new(b"CREATE Cash");
+        let mut stack = FlapJackStackBuilder::from_store(store.clone(), None)
+            .unwrap()
+            .build()
+            .unwrap();
+        stack.increment_wallet_amount("Cash", 5.0, None).unwrap();
+        assert_eq!(store.contents(), b"CREATE Cash\nINCREMENT Cash 5");
+
+        // an encrypted log can only be rewritten
+        let key = EncryptionKey::new("correct horse").unwrap();
+        stack.files[0].key = Some(key);
+        stack.decrement_wallet_amount("Cash", 2.0, None).unwrap();
+        let (log, _) = encryption::decrypt(&store.contents(), "correct horse").unwrap();
+        assert_eq!(log, "CREATE Cash\nINCREMENT Cash 5\nDECREMENT Cash 2");
+    }
+}
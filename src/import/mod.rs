@@ -0,0 +1,539 @@
+use chrono::NaiveDate;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use crate::flapjack_stack::flapjack::{Amount, Directive, FlapJack};
+use crate::flapjack_stack::lexer;
+use crate::flapjack_stack::policy::Overdraft;
+use crate::flapjack_stack::{FlapJackStack, PushError};
+
+pub mod ofx;
+pub mod qif;
+
+/// The format dates are written in when they are stored at the start of a comment.
+pub const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// Starts the trailing comment that keeps the bank's id of an imported transaction.
+pub const FITID_TAG: &str = "fitid:";
+
+#[non_exhaustive]
+#[derive(thiserror::Error, Debug)]
+pub enum ImportError {
+    #[error("Could not read statement file. ({0})")]
+    CouldNotReadFile(#[source] std::io::Error),
+    #[error("Unsupported statement format \"{0}\". Expected .ofx, .qfx or .qif.")]
+    UnsupportedFormat(String),
+    #[error("Could not parse amount \"{0}\".")]
+    InvalidAmount(String),
+    #[error("Could not parse date \"{0}\".")]
+    InvalidDate(String),
+    #[error("No transactions were found in the statement.")]
+    NoTransactionsFound,
+}
+
+/// The transactions of a single account in a bank export.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Statement {
+    /// The account id (OFX) or account name (QIF) the bank gave the account, if any.
+    pub account_id: Option<String>,
+    pub transactions: Vec<StatementTransaction>,
+}
+
+impl Statement {
+    /// Turns every transaction into an `ImportCandidate` for `wallet`.
+    pub fn to_candidates(&self, wallet: &str) -> Vec<ImportCandidate> {
+        self.transactions
+            .iter()
+            .map(|transaction| ImportCandidate {
+                wallet: wallet.to_owned(),
+                amount: transaction.amount,
+                date: transaction.date,
+                comment: transaction.comment(),
+                id: transaction.id.clone(),
+            })
+            .collect()
+    }
+}
+
+/// A transaction as the bank describes it. A negative amount is money leaving the account.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct StatementTransaction {
+    pub date: Option<NaiveDate>,
+    pub amount: f64,
+    pub payee: Option<String>,
+    pub memo: Option<String>,
+    /// The id the bank gave the transaction (the OFX FITID), if the export has one.
+    pub id: Option<String>,
+}
+
+impl StatementTransaction {
+    /// Combines the payee and the memo into a single comment.
+    pub fn comment(&self) -> Option<String> {
+        match (&self.payee, &self.memo) {
+            (Some(payee), Some(memo)) if payee != memo => Some(format!("{payee} - {memo}")),
+            (Some(payee), _) => Some(payee.clone()),
+            (None, Some(memo)) => Some(memo.clone()),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Reads a bank export, picking the parser from the file extension.
+pub fn parse_statement_file(path: &str) -> Result<Vec<Statement>, ImportError> {
+    let extension = Path::new(path)
+        .extension()
+        .map(|x| x.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    let content = fs::read_to_string(path).map_err(ImportError::CouldNotReadFile)?;
+
+    match extension.as_str() {
+        "ofx" | "qfx" => ofx::parse(&content),
+        "qif" => qif::parse(&content),
+        _ => Err(ImportError::UnsupportedFormat(extension)),
+    }
+}
+
+/// Remembers which wallet a bank account id should be imported into,
+/// so the user only has to be asked once per account.
+/// Stored as one `"account id" "wallet name"` pair per line.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct AccountMap {
+    pub wallets: HashMap<String, String>,
+}
+
+impl AccountMap {
+    pub fn new(raw: &str) -> Self {
+        let mut wallets = HashMap::new();
+
+        for line in raw.lines() {
+            let split = lexer::tokenize(line).unwrap_or_default().params;
+            if let [account_id, wallet] = split.as_slice() {
+                wallets.insert(account_id.clone(), wallet.clone());
+            }
+        }
+
+        Self { wallets }
+    }
+
+    /// Returns an empty map if the file does not exist yet.
+    pub fn from_file(path: &str) -> Self {
+        let content = fs::read_to_string(path).unwrap_or_default();
+        Self::new(&content)
+    }
+
+    pub fn wallet(&self, account_id: &str) -> Option<&String> {
+        self.wallets.get(account_id)
+    }
+
+    pub fn insert(&mut self, account_id: &str, wallet: &str) {
+        self.wallets
+            .insert(account_id.to_owned(), wallet.to_owned());
+    }
+
+    pub fn serialize(&self) -> String {
+        let mut account_ids = self.wallets.keys().collect::<Vec<&String>>();
+        account_ids.sort();
+
+        account_ids
+            .into_iter()
+            .map(|account_id| {
+                format!(
+                    "{} {}",
+                    lexer::quote(account_id),
+                    lexer::quote(&self.wallets[account_id])
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    pub fn serialize_to_file(&self, path: &str) -> std::io::Result<()> {
+        fs::write(path, self.serialize())
+    }
+}
+
+/// A single transaction read from a bank export that has not been written to the log yet.
+/// A positive amount becomes an INCREMENT and a negative amount becomes a DECREMENT.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ImportCandidate {
+    pub wallet: String,
+    pub amount: f64,
+    pub date: Option<NaiveDate>,
+    pub comment: Option<String>,
+    /// The id the bank gave the transaction, if the export has one.
+    pub id: Option<String>,
+}
+
+impl ImportCandidate {
+    /// The `fitid:<id>` trailing comment that keeps the bank's id in the log.
+    pub fn note(&self) -> Option<String> {
+        let id = self.id.as_ref()?.split_whitespace().collect::<String>();
+        Some(format!("{}{}", FITID_TAG, id))
+    }
+
+    /// Converts the candidate into the same directive a manual INCREMENT or DECREMENT would produce.
+    /// The date (if any) is stored at the start of the comment so it survives in the log.
+    pub fn to_flapjack(&self) -> FlapJack {
+        let wallet = self.wallet.clone();
+        let amount = Amount::new(self.amount.abs());
+
+        let comment = match (self.date, &self.comment) {
+            (Some(date), Some(comment)) => {
+                Some(format!("{} {}", date.format(DATE_FORMAT), comment))
+            }
+            (Some(date), None) => Some(date.format(DATE_FORMAT).to_string()),
+            (None, Some(comment)) => Some(comment.clone()),
+            (None, None) => None,
+        };
+
+        let directive = if self.amount < 0.0 {
+            Directive::Decrement {
+                wallet,
+                amount,
+                comment,
+            }
+        } else {
+            Directive::Increment {
+                wallet,
+                amount,
+                comment,
+            }
+        };
+
+        FlapJack::Directive(directive)
+    }
+}
+
+/// What two transactions need to have in common to be considered the same transaction.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct Fingerprint {
+    pub wallet: String,
+    /// The signed amount in cents, so that float noise does not matter.
+    pub cents: i64,
+    pub date: Option<NaiveDate>,
+    pub comment: String,
+}
+
+impl Fingerprint {
+    pub fn from_candidate(candidate: &ImportCandidate) -> Self {
+        Self {
+            wallet: candidate.wallet.clone(),
+            cents: to_cents(candidate.amount),
+            date: candidate.date,
+            comment: normalize_comment(candidate.comment.as_deref().unwrap_or("")),
+        }
+    }
+
+    /// Returns None for directives that do not move money (CREATE, SET, DESTROY, unknown commands).
+    pub fn from_directive(directive: &Directive) -> Option<Self> {
+        let (wallet, amount) = match directive {
+            Directive::Increment { wallet, amount, .. } => (wallet, amount.value()),
+            Directive::Decrement { wallet, amount, .. } => (wallet, -amount.value()),
+            _ => return None,
+        };
+
+        let (date, comment) = split_date(directive.comment().unwrap_or(""));
+
+        Some(Self {
+            wallet: wallet.clone(),
+            cents: to_cents(amount),
+            date,
+            comment: normalize_comment(comment),
+        })
+    }
+}
+
+/// A row in an `ImportPreview`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ImportRow {
+    pub candidate: ImportCandidate,
+    /// Whether the transaction looks like it is already in the log.
+    pub duplicate: bool,
+    /// Whether the transaction will be written when the import is applied.
+    pub include: bool,
+}
+
+/// The list of transactions that an import would write, before anything is written.
+/// Likely duplicates are flagged and excluded by default, but each row can be toggled.
+#[derive(Debug, PartialEq, Clone)]
+pub struct ImportPreview {
+    pub rows: Vec<ImportRow>,
+}
+
+impl ImportPreview {
+    /// A transaction with a bank id is a duplicate if a directive of its wallet has that id.
+    /// Otherwise it is matched by its fingerprint against directives without an id, which were
+    /// written by hand or imported from an export that has no ids.
+    pub fn new(candidates: Vec<ImportCandidate>, stack: &FlapJackStack) -> Self {
+        // Count the existing fingerprints so that two identical transactions on the same day
+        // (two coffees, for example) are only matched against two existing entries.
+        let mut existing: HashMap<Fingerprint, usize> = HashMap::new();
+        let mut notes: HashSet<(&str, String)> = HashSet::new();
+        for (i, flapjack) in stack.flapjacks.iter().enumerate() {
+            let directive = match flapjack {
+                FlapJack::Directive(x) => x,
+                _ => continue,
+            };
+            let note = stack.trailing_comment(i).and_then(|x| {
+                x.split_whitespace()
+                    .find(|word| word.starts_with(FITID_TAG))
+                    .map(|word| word.to_owned())
+            });
+
+            match (note, directive.wallet()) {
+                (Some(note), Some(wallet)) => {
+                    notes.insert((wallet, note));
+                }
+                _ => {
+                    if let Some(fingerprint) = Fingerprint::from_directive(directive) {
+                        *existing.entry(fingerprint).or_insert(0) += 1;
+                    }
+                }
+            }
+        }
+
+        let rows = candidates
+            .into_iter()
+            .map(|candidate| {
+                let fingerprint = Fingerprint::from_candidate(&candidate);
+                let duplicate = candidate
+                    .note()
+                    .is_some_and(|x| notes.contains(&(candidate.wallet.as_str(), x)))
+                    || match existing.get_mut(&fingerprint) {
+                        Some(count) if *count > 0 => {
+                            *count -= 1;
+                            true
+                        }
+                        _ => false,
+                    };
+
+                ImportRow {
+                    candidate,
+                    duplicate,
+                    include: !duplicate,
+                }
+            })
+            .collect();
+
+        Self { rows }
+    }
+
+    /// Flips whether the row at `index` will be imported.
+    pub fn toggle(&mut self, index: usize) {
+        if let Some(row) = self.rows.get_mut(index) {
+            row.include = !row.include;
+        }
+    }
+
+    pub fn duplicate_count(&self) -> usize {
+        self.rows.iter().filter(|row| row.duplicate).count()
+    }
+
+    pub fn included(&self) -> impl Iterator<Item = &ImportCandidate> {
+        self.rows
+            .iter()
+            .filter(|row| row.include)
+            .map(|row| &row.candidate)
+    }
+}
+
+/// Splits a leading `YYYY-MM-DD` date off of a comment, if there is one.
+pub fn split_date(comment: &str) -> (Option<NaiveDate>, &str) {
+    let trimmed = comment.trim_start();
+    let date_part = trimmed.get(..10).unwrap_or("");
+
+    match NaiveDate::parse_from_str(date_part, DATE_FORMAT) {
+        Ok(date) => (Some(date), trimmed[10..].trim_start()),
+        Err(_) => (None, comment),
+    }
+}
+
+/// Lowercases the comment and collapses all whitespace so that small formatting
+/// differences between exports do not hide a duplicate.
+pub fn normalize_comment(comment: &str) -> String {
+    comment
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Parses an amount like `-1,200.50` or `+4.50`.
+/// A comma is treated as the decimal separator when there is no dot.
+pub fn parse_amount(value: &str) -> Result<f64, ImportError> {
+    let trimmed = value.trim().trim_start_matches('+');
+    let cleaned = if trimmed.contains('.') {
+        trimmed.replace(',', "")
+    } else {
+        trimmed.replace(',', ".")
+    };
+
+    cleaned
+        .parse::<f64>()
+        .map_err(|_| ImportError::InvalidAmount(value.to_owned()))
+}
+
+fn to_cents(amount: f64) -> i64 {
+    (amount * 100.0).round() as i64
+}
+
+impl FlapJackStack {
+    /// Writes every included row of the preview to the log, with the bank's id of each
+    /// transaction in a `# fitid:<id>` comment. Nothing is written if any row would overdraw a
+    /// wallet that forbids it. Returns the overdrafts of wallets that only warn.
+    pub fn apply_import(&mut self, preview: &ImportPreview) -> Result<Vec<Overdraft>, PushError> {
+        let flapjacks = preview
+            .included()
+            .map(|x| x.to_flapjack())
+            .collect::<Vec<FlapJack>>();
+        let warnings = self.check_batch(&flapjacks)?;
+
+        for (flapjack, candidate) in flapjacks.into_iter().zip(preview.included()) {
+            match candidate.note() {
+                Some(note) => self.push_flap_with_note(flapjack, &note)?,
+                None => self.push_flap(flapjack)?,
+            }
+        }
+        Ok(warnings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ImportCandidate, ImportPreview};
+    use crate::flapjack_stack::flapjack_stack_builder::FlapJackStackBuilder;
+    use crate::flapjack_stack::PushError;
+    use chrono::NaiveDate;
+
+    fn candidate(amount: f64, day: u32, comment: &str) -> ImportCandidate {
+        ImportCandidate {
+            wallet: "Checking (Bank)".to_owned(),
+            amount,
+            date: NaiveDate::from_ymd_opt(2026, 9, day),
+            comment: Some(comment.to_owned()),
+            id: None,
+        }
+    }
+
+    #[test]
+    fn test_duplicates_are_flagged_and_skipped() {
+        let log = "
+        CREATE \"Checking (Bank)\"
+        INCREMENT \"Checking (Bank)\" 1200 \"2026-09-01 Payroll\"
+        DECREMENT \"Checking (Bank)\" 4.50 \"2026-09-02 coffee  shop\"
+        ";
+        let stack = FlapJackStackBuilder::new(log, None).build().unwrap();
+
+        let candidates = vec![
+            candidate(1200.0, 1, "PAYROLL"),
+            candidate(-4.5, 2, "Coffee Shop"),
+            candidate(-4.5, 2, "Coffee Shop"),
+            candidate(-30.0, 3, "Groceries"),
+        ];
+
+        let preview = ImportPreview::new(candidates, &stack);
+        let duplicates = preview
+            .rows
+            .iter()
+            .map(|row| row.duplicate)
+            .collect::<Vec<bool>>();
+
+        assert_eq!(duplicates, vec![true, true, false, false]);
+        assert_eq!(preview.duplicate_count(), 2);
+        assert_eq!(preview.included().count(), 2);
+    }
+
+    #[test]
+    fn test_toggle_overrides_default() {
+        let log = "
+        CREATE \"Checking (Bank)\"
+        DECREMENT \"Checking (Bank)\" 30 \"2026-09-03 Groceries\"
+        ";
+        let stack = FlapJackStackBuilder::new(log, None).build().unwrap();
+
+        let mut preview = ImportPreview::new(vec![candidate(-30.0, 3, "Groceries")], &stack);
+        assert_eq!(preview.included().count(), 0);
+
+        preview.toggle(0);
+        assert_eq!(preview.included().count(), 1);
+    }
+
+    #[test]
+    fn test_imported_directive_is_detected_on_reimport() {
+        let first = candidate(-12.25, 5, "Bookstore");
+        let log = format!(
+            "CREATE \"Checking (Bank)\"\n{}",
+            first.to_flapjack().serialize()
+        );
+        let stack = FlapJackStackBuilder::new(&log, None).build().unwrap();
+
+        let preview = ImportPreview::new(vec![first], &stack);
+        assert!(preview.rows[0].duplicate);
+    }
+
+    #[test]
+    fn test_bank_ids_tell_transactions_apart() {
+        let log = "CREATE \"Checking (Bank)\"\n";
+        let mut stack = FlapJackStackBuilder::new(log, None).build().unwrap();
+        let coffee = |id: &str| ImportCandidate {
+            id: Some(id.to_owned()),
+            ..candidate(-4.5, 2, "Coffee Shop")
+        };
+
+        let preview = ImportPreview::new(vec![coffee("A1")], &stack);
+        stack.apply_import(&preview).unwrap();
+        assert!(stack.serialize().ends_with(
+            "DECREMENT \"Checking (Bank)\" 4.5 \"2026-09-02 Coffee Shop\" # fitid:A1\n"
+        ));
+
+        // the same coffee again is a duplicate, but a second coffee that day is not
+        let preview = ImportPreview::new(vec![coffee("A1"), coffee("A2")], &stack);
+        let duplicates = preview
+            .rows
+            .iter()
+            .map(|row| row.duplicate)
+            .collect::<Vec<bool>>();
+        assert_eq!(duplicates, vec![true, false]);
+
+        // a directive without an id is still matched by what it says
+        let log = "CREATE \"Checking (Bank)\"\nDECREMENT \"Checking (Bank)\" 4.5 \"2026-09-02 coffee shop\"\n";
+        let stack = FlapJackStackBuilder::new(log, None).build().unwrap();
+        let preview = ImportPreview::new(vec![coffee("A1"), coffee("A2")], &stack);
+        assert_eq!(preview.duplicate_count(), 1);
+    }
+
+    #[test]
+    fn test_import_past_a_warn_limit_is_written_with_warnings() {
+        let log = "CREATE \"Checking (Bank)\" policy=warn\n";
+        let mut stack = FlapJackStackBuilder::new(log, None).build().unwrap();
+        let preview = ImportPreview::new(vec![candidate(-30.0, 2, "Groceries")], &stack);
+
+        let warnings = stack.apply_import(&preview).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].balance, -30.0);
+        assert_eq!(stack.amount("Checking (Bank)"), -30.0);
+    }
+
+    #[test]
+    fn test_forbidden_import_writes_nothing() {
+        let log = "CREATE \"Checking (Bank)\" policy=forbid\nINCREMENT \"Checking (Bank)\" 50\n";
+        let mut stack = FlapJackStackBuilder::new(log, None).build().unwrap();
+
+        // the deposit comes first, but the spending after it still goes past zero
+        let candidates = vec![
+            candidate(20.0, 1, "Refund"),
+            candidate(-30.0, 2, "Groceries"),
+            candidate(-45.0, 3, "Shoes"),
+        ];
+        let preview = ImportPreview::new(candidates, &stack);
+
+        assert!(matches!(
+            stack.apply_import(&preview),
+            Err(PushError::Overdraft(_))
+        ));
+        assert_eq!(stack.serialize(), log);
+        assert_eq!(stack.amount("Checking (Bank)"), 50.0);
+    }
+}
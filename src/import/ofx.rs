@@ -0,0 +1,123 @@
+use chrono::NaiveDate;
+use regex::Regex;
+
+use super::{parse_amount, ImportError, Statement, StatementTransaction};
+
+/// Parses an OFX or QFX file into one `Statement` per account.
+/// Both the SGML flavour (OFX 1.x, where leaf elements are not closed)
+/// and the XML flavour (OFX 2.x) are handled by the same tag scanner.
+pub fn parse(content: &str) -> Result<Vec<Statement>, ImportError> {
+    let re = Regex::new(r"<(/?)([A-Za-z0-9.]+)>([^<]*)").unwrap();
+
+    let mut statements: Vec<Statement> = Vec::new();
+    let mut account_id: Option<String> = None;
+    let mut transactions: Vec<StatementTransaction> = Vec::new();
+    let mut current: Option<StatementTransaction> = None;
+
+    for capture in re.captures_iter(content) {
+        let closing = &capture[1] == "/";
+        let tag = capture[2].to_uppercase();
+        let value = capture[3].trim();
+
+        match (closing, tag.as_str()) {
+            (false, "STMTRS") | (false, "CCSTMTRS") => {
+                account_id = None;
+                transactions = Vec::new();
+            }
+            (true, "STMTRS") | (true, "CCSTMTRS") => {
+                statements.push(Statement {
+                    account_id: account_id.take(),
+                    transactions: std::mem::take(&mut transactions),
+                });
+            }
+            (false, "STMTTRN") => {
+                current = Some(StatementTransaction::default());
+            }
+            (true, "STMTTRN") => {
+                if let Some(transaction) = current.take() {
+                    transactions.push(transaction);
+                }
+            }
+            (false, "ACCTID") if current.is_none() => {
+                account_id = Some(value.to_owned());
+            }
+            (false, _) => {
+                if let Some(transaction) = current.as_mut() {
+                    apply_field(transaction, &tag, value)?;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    // An SGML file may leave the last transaction or statement unclosed
+    if let Some(transaction) = current.take() {
+        transactions.push(transaction);
+    }
+
+    if !transactions.is_empty() {
+        statements.push(Statement {
+            account_id,
+            transactions,
+        });
+    }
+
+    if statements.is_empty() {
+        return Err(ImportError::NoTransactionsFound);
+    }
+
+    Ok(statements)
+}
+
+fn apply_field(
+    transaction: &mut StatementTransaction,
+    tag: &str,
+    value: &str,
+) -> Result<(), ImportError> {
+    if value.is_empty() {
+        return Ok(());
+    }
+
+    match tag {
+        "TRNAMT" => transaction.amount = parse_amount(value)?,
+        "DTPOSTED" => transaction.date = Some(parse_date(value)?),
+        "NAME" | "PAYEE" => transaction.payee = Some(value.to_owned()),
+        "MEMO" => transaction.memo = Some(value.to_owned()),
+        "FITID" => transaction.id = Some(value.to_owned()),
+        _ => (),
+    }
+
+    Ok(())
+}
+
+/// OFX dates look like `20260914`, `20260914120000` or `20260914120000.000[-5:EST]`.
+/// Only the day is kept.
+fn parse_date(value: &str) -> Result<NaiveDate, ImportError> {
+    let day = value.get(..8).unwrap_or(value);
+    NaiveDate::parse_from_str(day, "%Y%m%d").map_err(|_| ImportError::InvalidDate(value.to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_sgml_without_closing_tags() {
+        let content = "OFXHEADER:100
+<OFX><BANKMSGSRSV1><STMTTRNRS><STMTRS>
+<BANKACCTFROM><ACCTID>12345</BANKACCTFROM>
+<BANKTRANLIST>
+<STMTTRN><TRNTYPE>DEBIT<DTPOSTED>20260902120000.000[-5:EST]<TRNAMT>-4.50<NAME>COFFEE SHOP</STMTTRN>
+</BANKTRANLIST></STMTRS></STMTTRNRS></BANKMSGSRSV1></OFX>";
+
+        let statements = parse(content).unwrap();
+        assert_eq!(statements.len(), 1);
+        assert_eq!(statements[0].account_id, Some("12345".to_owned()));
+
+        let transaction = &statements[0].transactions[0];
+        assert_eq!(transaction.amount, -4.5);
+        assert_eq!(transaction.date, NaiveDate::from_ymd_opt(2026, 9, 2));
+        assert_eq!(transaction.payee, Some("COFFEE SHOP".to_owned()));
+    }
+}
@@ -0,0 +1,127 @@
+use chrono::NaiveDate;
+
+use super::{parse_amount, ImportError, Statement, StatementTransaction};
+
+/// Parses a QIF file into one `Statement` per account.
+/// Transactions that appear before any `!Account` block end up in a statement without an account id.
+pub fn parse(content: &str) -> Result<Vec<Statement>, ImportError> {
+    let mut statements: Vec<Statement> = Vec::new();
+    let mut statement = Statement::default();
+    let mut current = StatementTransaction::default();
+    let mut has_fields = false;
+    let mut in_account_block = false;
+    let mut account_name: Option<String> = None;
+
+    for line in content.lines() {
+        let line = line.trim_end_matches('\r');
+        let mut chars = line.chars();
+        let code = match chars.next() {
+            Some(code) => code,
+            None => continue,
+        };
+        let value = chars.as_str().trim();
+
+        if code == '!' {
+            if value.eq_ignore_ascii_case("Account") {
+                in_account_block = true;
+                account_name = None;
+            }
+            continue;
+        }
+
+        if in_account_block {
+            match code {
+                'N' => account_name = Some(value.to_owned()),
+                '^' => {
+                    in_account_block = false;
+                    if !statement.transactions.is_empty() {
+                        statements.push(std::mem::take(&mut statement));
+                    }
+                    statement.account_id = account_name.take();
+                }
+                _ => (),
+            }
+            continue;
+        }
+
+        match code {
+            'D' => current.date = Some(parse_date(value)?),
+            'T' | 'U' => current.amount = parse_amount(value)?,
+            'P' => current.payee = Some(value.to_owned()),
+            'M' => current.memo = Some(value.to_owned()),
+            '^' => {
+                if has_fields {
+                    statement.transactions.push(std::mem::take(&mut current));
+                }
+                has_fields = false;
+                continue;
+            }
+            _ => continue,
+        }
+
+        has_fields = true;
+    }
+
+    if !statement.transactions.is_empty() {
+        statements.push(statement);
+    }
+
+    if statements.is_empty() {
+        return Err(ImportError::NoTransactionsFound);
+    }
+
+    Ok(statements)
+}
+
+/// QIF dates are not standardized. Banks commonly use `MM/DD/YYYY`, `MM/DD/YY`,
+/// `MM/DD'YY` (for years after 2000), `MM-DD-YYYY` or `YYYY-MM-DD`.
+fn parse_date(value: &str) -> Result<NaiveDate, ImportError> {
+    let normalized = value.replace(['\'', '-'], "/").replace(' ', "");
+    let parts = normalized.split('/').collect::<Vec<&str>>();
+
+    // chrono's %Y happily reads "26" as the year 26, so the format is picked by the shape of the date
+    let format = match parts.as_slice() {
+        [year, _, _] if year.len() == 4 => "%Y/%m/%d",
+        [_, _, year] if year.len() == 2 => "%m/%d/%y",
+        _ => "%m/%d/%Y",
+    };
+
+    NaiveDate::parse_from_str(&normalized, format)
+        .map_err(|_| ImportError::InvalidDate(value.to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_account_blocks_split_statements() {
+        let content = "!Account
+NChecking
+^
+!Type:Bank
+D09/01/2026
+T1,200.00
+PPayroll
+^
+!Account
+NCash
+^
+!Type:Cash
+D9/3'26
+T-12.00
+MLunch
+^";
+
+        let statements = parse(content).unwrap();
+        assert_eq!(statements.len(), 2);
+        assert_eq!(statements[0].account_id, Some("Checking".to_owned()));
+        assert_eq!(statements[0].transactions[0].amount, 1200.0);
+        assert_eq!(statements[1].account_id, Some("Cash".to_owned()));
+        assert_eq!(
+            statements[1].transactions[0].date,
+            NaiveDate::from_ymd_opt(2026, 9, 3)
+        );
+    }
+}
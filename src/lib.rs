@@ -1,5 +1,9 @@
 #[macro_use]
 extern crate prettytable;
+pub mod cli;
+pub mod encryption;
+pub mod export;
 pub mod file_io;
 pub mod flapjack_stack;
+pub mod import;
 pub mod option_repl;
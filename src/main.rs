@@ -1,19 +1,47 @@
-#[macro_use]
-extern crate prettytable;
+use std::env;
+use std::process;
 
-mod file_io;
-mod flapjack_stack;
-mod option_repl;
-
-use flapjack_stack::flapjack_stack_builder::FlapJackStackBuilder;
-use option_repl::OptionRepl;
+use chrono::Local;
+use flapjack::cli;
+use flapjack::file_io;
+use flapjack::option_repl::OptionRepl;
 
 // TODO: show last comment on the table (maybe)
 // or add a way to check comments without using the log
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = env::args().skip(1).collect::<Vec<String>>();
+
+    // any arguments means a one-off command instead of the interactive menu
+    if !args.is_empty() {
+        match cli::run(&args) {
+            Ok(0) => return Ok(()),
+            Ok(code) => process::exit(code),
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+    }
+
     let path = file_io::init_log_db()?;
 
-    let stack = FlapJackStackBuilder::from_file(&path).build();
+    let mut stack = cli::load_stack(&path)?;
+    match stack.run_schedule(Local::now().date_naive()) {
+        Ok(run) => {
+            if !run.added.is_empty() {
+                println!("Added {} scheduled directives.", run.added.len());
+            }
+            for (_, overdraft) in run.warnings {
+                println!("Warning: {}", overdraft);
+            }
+            // the menu still opens, so the wallet can be topped up
+            for (occurrence, overdraft) in run.blocked {
+                println!("Skipped {}: {}", occurrence.flapjack.serialize(), overdraft);
+            }
+        }
+        Err(e) => println!("Could not add the scheduled directives: {}", e),
+    }
+
     let repl = OptionRepl::new(stack);
     repl.start();
 
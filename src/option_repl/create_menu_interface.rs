@@ -1,11 +1,27 @@
 use super::{OptionRepl, State};
+use crate::flapjack_stack::flapjack::Amount;
+use crate::flapjack_stack::policy::{Policy, WalletPolicy};
+use crate::flapjack_stack::PushError;
 
 impl OptionRepl {
     pub(super) fn create_menu_interface(&mut self) {
         println!("Wallet Name: ");
         let name = Self::wait_for_input();
 
-        println!("The wallet will be named {}. Confirm? (Y/n)", name);
+        let policy = Self::ask_for_policy();
+
+        println!("Details as key=value, like institution=Chase last4=1234 (hit enter for none):");
+        let meta = Self::ask_for_meta();
+
+        match &policy {
+            Some(policy) => println!(
+                "The wallet will be named {} and {} going below {:.2}. Confirm? (Y/n)",
+                name,
+                policy.policy,
+                policy.floor()
+            ),
+            None => println!("The wallet will be named {}. Confirm? (Y/n)", name),
+        }
 
         loop {
             let answer = Self::wait_for_input().to_lowercase();
@@ -13,8 +29,10 @@ impl OptionRepl {
 
             match trimmed {
                 "" | "y" => {
-                    self.stack.create_wallet(&name);
-                    println!("Created wallet: {}", name);
+                    match self.stack.create_wallet(&name, policy, meta) {
+                        Ok(()) => println!("Created wallet: {}", name),
+                        Err(e) => println!("{}", PushError::from(e)),
+                    }
                     break;
                 }
                 "n" => {
@@ -30,4 +48,37 @@ impl OptionRepl {
 
         self.state = State::Default;
     }
+
+    // None if the wallet can go as low as it likes
+    fn ask_for_policy() -> Option<WalletPolicy> {
+        println!("Below zero: Allow[0] Warn[1] Forbid[2] (hit enter to allow)");
+        let policy = loop {
+            match Self::wait_for_input().as_str() {
+                "" | "0" => return None,
+                "1" => break Policy::Warn,
+                "2" => break Policy::Forbid,
+                _ => {
+                    println!("Please pick one of the options!");
+                    continue;
+                }
+            }
+        };
+
+        println!("Credit limit (hit enter for none): ");
+        let limit = loop {
+            let input = Self::wait_for_input();
+            if input.is_empty() {
+                break None;
+            }
+            match Amount::parse(&input) {
+                Some(x) => break Some(x),
+                None => {
+                    println!("Please enter a number!");
+                    continue;
+                }
+            }
+        };
+
+        Some(WalletPolicy { policy, limit })
+    }
 }
@@ -1,4 +1,5 @@
 use super::{OptionRepl, State};
+use crate::flapjack_stack::PushError;
 
 impl OptionRepl {
     pub(super) fn destroy_menu_interface(&mut self) {
@@ -24,8 +25,10 @@ impl OptionRepl {
 
             match trimmed {
                 "y" => {
-                    self.stack.destroy_wallet(&chosen_wallet);
-                    println!("Destroyed wallet: {}", chosen_wallet);
+                    match self.stack.destroy_wallet(&chosen_wallet) {
+                        Ok(()) => println!("Destroyed wallet: {}", chosen_wallet),
+                        Err(e) => println!("{}", PushError::from(e)),
+                    }
                     break;
                 }
                 "" | "n" => {
@@ -0,0 +1,71 @@
+use super::{OptionRepl, State};
+use crate::flapjack_stack::flapjack::{Directive, FlapJack};
+use crate::flapjack_stack::PushError;
+use prettytable::Table;
+use std::io::{self, Write};
+
+impl OptionRepl {
+    pub(super) fn details_menu_interface(&mut self) {
+        let question = "Show the details of which wallet?: ";
+        let chosen_wallet_option = self.tell_user_to_pick_wallet(question);
+
+        let chosen_wallet = match chosen_wallet_option {
+            Some(x) => x,
+            None => {
+                self.state = State::Default;
+                return;
+            }
+        };
+
+        Self::print_divider();
+        // DO NOT USE table.printstd() IT DOES NOT WORK RIGHT ON WINDOWS
+        print!("{}", self.details_table(&chosen_wallet));
+        io::stdout().flush().unwrap();
+
+        println!("Change details as key=value, with nothing after the = to remove one (hit enter to go back):");
+        let meta = Self::ask_for_meta();
+        if !meta.is_empty() {
+            match self.stack.set_wallet_meta(&chosen_wallet, meta) {
+                Ok(()) => println!("Changed the details of wallet {}.", chosen_wallet),
+                Err(e) => println!("{}", PushError::from(e)),
+            }
+        }
+
+        self.state = State::Default;
+    }
+
+    fn details_table(&self, wallet: &str) -> String {
+        let mut table = Table::new();
+        table.add_row(row!["Wallet", wallet]);
+        table.add_row(row!["Amount", format!("{:.2}", self.stack.amount(wallet))]);
+
+        let policy = match self.stack.db.policies.get(wallet) {
+            Some(policy) => format!(
+                "{} (limit {:.2})",
+                policy.policy,
+                policy.limit.as_ref().map(|x| x.value()).unwrap_or(0.0)
+            ),
+            None => "allow".to_owned(),
+        };
+        table.add_row(row!["Policy", policy]);
+
+        // the wallet is the one made by the latest CREATE with its name
+        let created = self.stack.flapjacks.iter().rposition(|x| {
+            matches!(x, FlapJack::Directive(Directive::Create { wallet: name, .. }) if name == wallet)
+        });
+        if let Some(i) = created {
+            let line = self.stack.line_number(i);
+            let created = match &self.stack.files[self.stack.origins[i]].path {
+                Some(path) => format!("{}:{}", path, line),
+                None => format!("line {}", line),
+            };
+            table.add_row(row!["Created", created]);
+        }
+
+        for (key, value) in self.stack.db.metadata.get(wallet).into_iter().flatten() {
+            table.add_row(row![key, value]);
+        }
+
+        table.to_string()
+    }
+}
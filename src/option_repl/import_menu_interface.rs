@@ -0,0 +1,165 @@
+use prettytable::Table;
+use std::io::{self, Write};
+use std::path::Path;
+
+use super::{OptionRepl, State};
+use crate::import::{self, AccountMap, ImportPreview, DATE_FORMAT};
+
+impl OptionRepl {
+    pub(super) fn import_menu_interface(&mut self) {
+        println!("Path to statement file (.ofx, .qfx, .qif): ");
+        let path = Self::wait_for_input();
+
+        let statements = match import::parse_statement_file(&path) {
+            Ok(x) => x,
+            Err(e) => {
+                println!("{}", e);
+                self.state = State::Default;
+                return;
+            }
+        };
+
+        let account_map_path = self.account_map_path();
+        let mut account_map = AccountMap::from_file(&account_map_path);
+        let mut candidates = Vec::new();
+
+        for statement in &statements {
+            let account_id = statement.account_id.clone().unwrap_or_default();
+
+            let mapped_wallet = account_map
+                .wallet(&account_id)
+                .filter(|wallet| self.stack.db.wallet_amounts.contains_key(*wallet))
+                .cloned();
+
+            let wallet = match mapped_wallet {
+                Some(x) => x,
+                None => {
+                    let question = format!(
+                        "Import account \"{}\" ({} transactions) into which wallet?: ",
+                        account_id,
+                        statement.transactions.len()
+                    );
+
+                    match self.tell_user_to_pick_wallet(&question) {
+                        Some(x) => {
+                            if !account_id.is_empty() {
+                                account_map.insert(&account_id, &x);
+                            }
+                            x
+                        }
+                        None => {
+                            println!("Skipped account \"{}\".", account_id);
+                            continue;
+                        }
+                    }
+                }
+            };
+
+            candidates.extend(statement.to_candidates(&wallet));
+        }
+
+        if let Err(e) = account_map.serialize_to_file(&account_map_path) {
+            println!("Could not save account mapping. ({})", e);
+        }
+
+        if candidates.is_empty() {
+            println!("Nothing to import.");
+            self.state = State::Default;
+            return;
+        }
+
+        let mut preview = ImportPreview::new(candidates, &self.stack);
+
+        loop {
+            Self::print_import_preview(&preview);
+            println!(
+                "{} of {} transactions will be imported ({} likely duplicates).",
+                preview.included().count(),
+                preview.rows.len(),
+                preview.duplicate_count()
+            );
+            println!("Enter a row number to toggle it. Import? (Y/n)");
+
+            let answer = Self::wait_for_input().to_lowercase();
+            let trimmed = answer.trim();
+
+            match trimmed {
+                "" | "y" => {
+                    let count = preview.included().count();
+                    match self.stack.apply_import(&preview) {
+                        Ok(warnings) => {
+                            println!("Imported {} transactions.", count);
+                            for overdraft in warnings {
+                                println!("Warning: {}", overdraft);
+                            }
+                        }
+                        Err(e) => println!("Did not import anything: {}", e),
+                    }
+                    break;
+                }
+                "n" => {
+                    println!("Did not import transactions.");
+                    break;
+                }
+                _ => match trimmed.parse::<usize>() {
+                    Ok(i) if i < preview.rows.len() => {
+                        Self::print_divider();
+                        preview.toggle(i);
+                    }
+                    _ => {
+                        println!("Invalid answer! Please answer with 'y', 'n', a row number or hit enter to accept the default.");
+                        continue;
+                    }
+                },
+            };
+        }
+
+        self.state = State::Default;
+    }
+
+    fn print_import_preview(preview: &ImportPreview) {
+        let mut table = Table::new();
+        table.add_row(row![
+            "#", "Date", "Wallet", "Amount", "Comment", "Status", "Import"
+        ]);
+
+        for (i, row) in preview.rows.iter().enumerate() {
+            let candidate = &row.candidate;
+            let date = candidate
+                .date
+                .map(|x| x.format(DATE_FORMAT).to_string())
+                .unwrap_or_default();
+            let status = if row.duplicate { "Duplicate" } else { "New" };
+            let include = if row.include { "yes" } else { "no" };
+
+            table.add_row(row![
+                i,
+                date,
+                candidate.wallet,
+                format!("{:.2}", candidate.amount),
+                candidate.comment.clone().unwrap_or_default(),
+                status,
+                include
+            ]);
+        }
+
+        // DO NOT USE table.printstd() IT DOES NOT WORK RIGHT ON WINDOWS
+        let str = table.to_string();
+        print!("{str}");
+        io::stdout().flush().unwrap();
+    }
+
+    // the account mapping lives next to the log
+    fn account_map_path(&self) -> String {
+        let log_path = self
+            .stack
+            .log_path
+            .clone()
+            .expect("There needs to be a log somewhere!");
+
+        Path::new(&log_path)
+            .with_file_name("accounts.map")
+            .to_string_lossy()
+            .to_string()
+    }
+}
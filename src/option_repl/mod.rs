@@ -1,4 +1,11 @@
-use crate::flapjack_stack::FlapJackStack;
+use crate::flapjack_stack::flapjack::split_meta;
+use crate::flapjack_stack::group::{groups_of, in_group, sort_as_tree, GROUP_SEPARATOR};
+use crate::flapjack_stack::history::Position;
+use crate::flapjack_stack::lexer;
+use crate::flapjack_stack::policy::Policy;
+use crate::flapjack_stack::{FlapJackDb, FlapJackStack};
+use crate::import::DATE_FORMAT;
+use chrono::{Local, NaiveDate};
 use prettytable::Table;
 use std::io::stdin;
 use std::io::{self, Write};
@@ -7,10 +14,12 @@ use std::process::exit;
 mod create_menu_interface;
 mod decrement_menu_interface;
 mod destroy_menu_interface;
+mod details_menu_interface;
+mod import_menu_interface;
 mod increment_menu_interface;
 mod set_menu_interface;
 
-const VALID_STATES: [State; 7] = [
+const VALID_STATES: [State; 10] = [
     State::SetMenu,
     State::IncrementMenu,
     State::DecrementMenu,
@@ -18,12 +27,16 @@ const VALID_STATES: [State; 7] = [
     State::DestroyMenu,
     State::View,
     State::Exit,
+    State::ImportMenu,
+    State::BudgetView,
+    State::DetailsMenu,
 ];
 
 #[derive(Clone, Copy)]
 pub enum State {
     Default,
     View,
+    BudgetView,
     Invalid,
     Exit,
     CreateMenu,
@@ -31,6 +44,8 @@ pub enum State {
     SetMenu,
     DestroyMenu,
     DecrementMenu,
+    ImportMenu,
+    DetailsMenu,
 }
 
 pub struct OptionRepl {
@@ -38,6 +53,86 @@ pub struct OptionRepl {
     state: State,
 }
 
+/// The balance of every wallet, or of the wallets in `group`, as a tree with a subtotal for each group.
+pub fn balance_table(db: &FlapJackDb, group: Option<&str>) -> String {
+    let wallets = match group {
+        Some(group) => db.wallet_names_in(group),
+        None => db.wallet_names(),
+    };
+
+    // every wallet and the groups it is in, each group once
+    let mut rows = wallets
+        .iter()
+        .flat_map(|x| groups_of(x).into_iter().chain(std::iter::once(x.as_str())))
+        .filter(|x| group.is_none_or(|group| in_group(x, group)))
+        .map(|x| x.to_owned())
+        .collect::<Vec<String>>();
+    sort_as_tree(&mut rows);
+    rows.dedup();
+
+    // Create the table
+    let mut table = Table::new();
+
+    let groups = db.groups();
+    let top_depth = group.map_or(0, |x| groups_of(x).len());
+    table.add_row(row!["Wallet", "Amount"]);
+    for name in rows.iter() {
+        // each row only shows the last part of its name, indented under the group it is in
+        let depth = groups_of(name).len() - top_depth;
+        let short_name = name.rsplit(GROUP_SEPARATOR).next().unwrap_or(name);
+        let label = format!("{}{}", "  ".repeat(depth), short_name);
+
+        let amount = if groups.contains(name) {
+            db.subtotal(name)
+        } else {
+            db.wallet_amounts[name]
+        };
+        // We make sure that the amount only has 2 decimal places
+        table.add_row(row![label, format!("{amount:.2}")]);
+    }
+
+    let total: f64 = wallets.iter().map(|x| db.wallet_amounts[x]).sum();
+
+    // We make sure that the total only has 2 decimal places
+    table.add_row(row!["Total", format!("{total:.2}")]);
+
+    table.to_string()
+}
+
+/// The progress of every savings goal, or None if the log has no goals.
+pub fn goal_table(stack: &FlapJackStack, today: NaiveDate) -> Option<String> {
+    let goals = stack.goals(today);
+    if goals.is_empty() {
+        return None;
+    }
+
+    let mut table = Table::new();
+    table.add_row(row!["Goal", "Saved", "Target", "Progress", "Projected"]);
+    for goal in goals.iter() {
+        let name = match &goal.description {
+            Some(description) => format!("{} ({})", goal.wallet, description),
+            None => goal.wallet.clone(),
+        };
+        let projected = if goal.is_reached() {
+            "reached".to_owned()
+        } else {
+            match goal.projected {
+                Some(date) => date.format(DATE_FORMAT).to_string(),
+                None => "-".to_owned(),
+            }
+        };
+        table.add_row(row![
+            name,
+            format!("{:.2}", goal.balance),
+            format!("{:.2}", goal.target),
+            format!("{:.0}%", goal.percent()),
+            projected
+        ]);
+    }
+
+    Some(table.to_string())
+}
+
 impl OptionRepl {
     pub fn new(stack: FlapJackStack) -> Self {
         Self {
@@ -66,6 +161,7 @@ impl OptionRepl {
         match &self.state {
             State::Default => self.handle_default(),
             State::View => self.view(),
+            State::BudgetView => self.budget_view(),
             State::Invalid => self.invalid(),
             State::Exit => self.exit(),
             State::CreateMenu => self.create_menu_interface(),
@@ -73,11 +169,13 @@ impl OptionRepl {
             State::SetMenu => self.set_menu_interface(),
             State::IncrementMenu => self.increment_menu_interface(),
             State::DecrementMenu => self.decrement_menu_interface(),
+            State::ImportMenu => self.import_menu_interface(),
+            State::DetailsMenu => self.details_menu_interface(),
         }
     }
 
     fn handle_default(&mut self) {
-        println!("Options: Set[0] Increment[1] Decrement[2] Create[3] Destroy[4] View[5] Exit[6]");
+        println!("Options: Set[0] Increment[1] Decrement[2] Create[3] Destroy[4] View[5] Exit[6] Import[7] Budgets[8] Details[9]");
 
         let input = Self::wait_for_input();
 
@@ -107,27 +205,106 @@ impl OptionRepl {
     }
 
     fn view(&mut self) {
-        // Create the table
-        let mut table = Table::new();
-
-        let mut total = 0.0;
-        table.add_row(row!["Wallet", "Amount"]);
-        let wallet_names = self.stack.return_wallet_names();
-        for name in wallet_names.iter() {
-            let amount = self.stack.amount(name);
-            total += amount;
-            // We make sure that the amount only has 2 decimal places
-            table.add_row(row![name, format!("{amount:.2}")]);
-        }
+        self.state = State::Default;
 
-        // We make sure that the total only has 2 decimal places
-        table.add_row(row!["Total", format!("{total:.2}")]);
+        println!("Show the balances as of a date (YYYY-MM-DD) or a line, or leave empty for now:");
+        let input = Self::wait_for_input();
+        let str = if input.is_empty() {
+            // goals are about the wallets as they are now, so they are left out of earlier balances
+            match goal_table(&self.stack, Local::now().date_naive()) {
+                Some(goals) => format!("{}{}", balance_table(&self.stack.db, None), goals),
+                None => balance_table(&self.stack.db, None),
+            }
+        } else {
+            match Position::parse(&input) {
+                Some(position) => balance_table(&self.stack.db_at(position), None),
+                None => {
+                    Self::print_divider();
+                    println!("Please enter a date or a line number!");
+                    return;
+                }
+            }
+        };
 
+        Self::print_divider();
         // DO NOT USE table.printstd() IT DOES NOT WORK RIGHT ON WINDOWS
-        let str = table.to_string();
         print!("{str}");
         io::stdout().flush().unwrap();
+    }
+
+    fn budget_view(&mut self) {
         self.state = State::Default;
+
+        let budgets = self.stack.budgets(Local::now().date_naive());
+        if budgets.is_empty() {
+            println!(
+                "There are no budgets. Add one to the log, like: BUDGET groceries 400 monthly"
+            );
+            return;
+        }
+
+        let mut table = Table::new();
+        table.add_row(row![
+            "Budget",
+            "Period",
+            "Spent",
+            "Allotted",
+            "Remaining",
+            "Spent %",
+            ""
+        ]);
+        for budget in budgets.iter() {
+            // over budget lines are marked, since colors do not survive table.to_string()
+            let status = if budget.is_over() { "OVER BUDGET" } else { "" };
+            table.add_row(row![
+                budget.name,
+                budget.period,
+                format!("{:.2}", budget.spent),
+                format!("{:.2}", budget.allotted),
+                format!("{:.2}", budget.remaining()),
+                format!("{:.0}%", budget.percent_spent()),
+                status
+            ]);
+        }
+
+        // DO NOT USE table.printstd() IT DOES NOT WORK RIGHT ON WINDOWS
+        print!("{}", table);
+        io::stdout().flush().unwrap();
+    }
+
+    // warns before a wallet goes past the limit of its policy, and returns false if the policy forbids it
+    fn check_overdraft(&self, wallet: &str, balance: f64) -> bool {
+        match self.stack.db.check_balance(wallet, balance) {
+            Some(overdraft) if overdraft.policy == Policy::Forbid => {
+                Self::print_divider();
+                println!("{}", overdraft);
+                false
+            }
+            Some(overdraft) => {
+                println!("Warning: {}", overdraft);
+                true
+            }
+            None => true,
+        }
+    }
+
+    // details like institution=Chase last4=1234, or none if the input is empty
+    fn ask_for_meta() -> Vec<(String, String)> {
+        loop {
+            let input = Self::wait_for_input();
+            let params = match lexer::tokenize(&input) {
+                Ok(x) => x.params,
+                Err(e) => {
+                    println!("{}", e);
+                    continue;
+                }
+            };
+
+            match params.iter().map(|x| split_meta(x)).collect() {
+                Some(meta) => return meta,
+                None => println!("Please enter details as key=value, like last4=1234!"),
+            }
+        }
     }
 
     fn wait_for_input() -> String {
@@ -147,32 +324,43 @@ impl OptionRepl {
 
     // return a Some<wallet_name> if a wallet was chosen, None if they chose back
     fn tell_user_to_pick_wallet(&self, question: &str) -> Option<String> {
-        let mut print_str = question.to_owned();
-        let wallet_names = self.stack.return_wallet_names();
-        let wallet_name_count = wallet_names.len() as i64;
-
-        for (i, wallet_name) in wallet_names.iter().enumerate() {
-            print_str.push_str(wallet_name);
-            print_str.push('[');
-            print_str.push_str(&i.to_string());
-            print_str.push(']');
-            if (i + 1) as i64 != wallet_name_count {
-                print_str.push(' ')
-            }
+        let mut wallet_names = self.stack.return_wallet_names();
+        let groups = self.stack.db.groups();
+        if !groups.is_empty() {
+            println!(
+                "Enter a group to only list the wallets in it: {}",
+                groups.join(" ")
+            );
         }
 
-        print_str.push_str(" BACK[");
-        print_str.push_str(&(wallet_name_count).to_string());
-        print_str.push(']');
-
-        let minimum = 0;
-        let maximum = wallet_name_count;
-
         // wait until a valid option is chosen
-        let choice_num: i64;
         loop {
+            let mut print_str = question.to_owned();
+            let wallet_name_count = wallet_names.len() as i64;
+
+            for (i, wallet_name) in wallet_names.iter().enumerate() {
+                print_str.push_str(wallet_name);
+                print_str.push('[');
+                print_str.push_str(&i.to_string());
+                print_str.push(']');
+                if (i + 1) as i64 != wallet_name_count {
+                    print_str.push(' ')
+                }
+            }
+
+            print_str.push_str(" BACK[");
+            print_str.push_str(&(wallet_name_count).to_string());
+            print_str.push(']');
+
+            let minimum = 0;
+            let maximum = wallet_name_count;
+
             println!("{}", print_str);
             let input = Self::wait_for_input();
+            if groups.contains(&input) {
+                wallet_names = self.stack.db.wallet_names_in(&input);
+                continue;
+            }
             if input.parse::<f64>().is_err() {
                 Self::print_divider();
                 println!("Please enter a number!");
@@ -186,16 +374,13 @@ impl OptionRepl {
                 continue;
             }
 
-            choice_num = num;
-            break;
-        }
-
-        // check to see if they wanted to go back
-        if choice_num == wallet_name_count {
-            None
-        } else {
-            let chosen_wallet = &wallet_names[choice_num as usize];
-            Some(chosen_wallet.clone())
+            // check to see if they wanted to go back
+            if num == wallet_name_count {
+                return None;
+            } else {
+                let chosen_wallet = &wallet_names[num as usize];
+                return Some(chosen_wallet.clone());
+            }
         }
     }
 }
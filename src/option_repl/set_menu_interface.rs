@@ -34,6 +34,11 @@ impl OptionRepl {
         println!("Enter comment: ");
         let comment = Self::wait_for_input();
 
+        if !self.check_overdraft(&chosen_wallet, amount) {
+            self.state = State::Default;
+            return;
+        }
+
         if !comment.is_empty() {
             println!("Wallet: {chosen_wallet}");
             println!("Amount: {amount:.2}");
@@ -52,14 +57,19 @@ impl OptionRepl {
 
             match trimmed {
                 "" | "y" => {
-                    if !comment.is_empty() {
+                    let pushed = if !comment.is_empty() {
                         self.stack
-                            .set_wallet_amount(&chosen_wallet, amount, Some(&comment));
+                            .set_wallet_amount(&chosen_wallet, amount, Some(&comment))
                     } else {
-                        self.stack.set_wallet_amount(&chosen_wallet, amount, None);
-                    }
+                        self.stack.set_wallet_amount(&chosen_wallet, amount, None)
+                    };
 
-                    println!("Set wallet {}'s amount to {:.2}", chosen_wallet, amount);
+                    match pushed {
+                        Ok(()) => {
+                            println!("Set wallet {}'s amount to {:.2}", chosen_wallet, amount);
+                        }
+                        Err(e) => println!("{}", e),
+                    }
                     break;
                 }
                 "n" => {
@@ -0,0 +1,19 @@
+use flapjack::export::beancount::BeancountExporter;
+use flapjack::flapjack_stack::flapjack_stack_builder::FlapJackStackBuilder;
+use std::fs;
+
+// expected.beancount is meant to pass `bean-check`, so run it through bean-check
+// again whenever this fixture is regenerated
+const INPUT_FLAP_PATH: &str = "tests/test_files/beancount/input.flap";
+const EXPECTED_PATH: &str = "tests/test_files/beancount/expected.beancount";
+
+#[test]
+fn beancount_matches_fixture() {
+    let stack = FlapJackStackBuilder::from_file(INPUT_FLAP_PATH)
+        .build()
+        .unwrap();
+    let exported = BeancountExporter::default().export(&stack);
+    let expected = fs::read_to_string(EXPECTED_PATH).unwrap();
+
+    assert_eq!(exported, expected);
+}
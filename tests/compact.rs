@@ -0,0 +1,98 @@
+use std::env;
+use std::fs;
+
+use chrono::{Datelike, Local, Months};
+use flapjack::cli;
+use flapjack::flapjack_stack::flapjack_stack_builder::FlapJackStackBuilder;
+
+#[test]
+fn compacting_keeps_what_the_log_is_still_using() {
+    let today = Local::now().date_naive();
+    let this_month = today.with_day(1).unwrap();
+    let last_month = this_month - Months::new(1);
+    let two_months_ago = this_month - Months::new(2);
+
+    let directory = env::temp_dir().join("flapjack_compact");
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    let path = directory.join("log.flap").to_string_lossy().to_string();
+
+    // the lines before CHAIN are not hashed, so they can be written by hand
+    let log = format!(
+        r#"CREATE Cash policy=forbid
+CREATE Savings "rainy days" policy=warn limit=100 bank=Chase
+META Savings last4=1234
+BUDGET groceries 300
+BUDGET groceries 400
+GOAL Savings 2000 "old roof"
+GOAL Savings 5000 "new roof"
+RECUR monthly 1 INCREMENT Savings 100 paycheck
+INCREMENT Savings 1000 "{} opening"
+INCREMENT Cash 200 "{} wages"
+CHAIN
+"#,
+        two_months_ago, last_month
+    );
+    fs::write(&path, log).unwrap();
+
+    let mut stack = FlapJackStackBuilder::from_file(&path).build().unwrap();
+    stack
+        .decrement_wallet_amount("Cash", 30.0, Some(&format!("{} groceries", this_month)))
+        .unwrap();
+    stack.run_schedule(today).unwrap();
+
+    let args = ["compact", "--log", &path, "--archive"]
+        .iter()
+        .map(|x| x.to_string())
+        .chain([directory.join("old.flap").to_string_lossy().to_string()])
+        .collect::<Vec<String>>();
+    assert_eq!(cli::run(&args).unwrap(), 0);
+
+    let compacted = FlapJackStackBuilder::from_file(&path).build().unwrap();
+    let serialized = compacted.serialize();
+    assert!(serialized.contains("\nCHAIN\n"));
+    assert!(!serialized.contains("old roof"));
+
+    // the chain and the archive hash both check out
+    assert!(compacted.verify().is_ok());
+
+    assert_eq!(compacted.db.wallet_amounts, stack.db.wallet_amounts);
+    assert_eq!(compacted.db.policies, stack.db.policies);
+    assert_eq!(compacted.db.metadata, stack.db.metadata);
+    assert_eq!(compacted.budgets(today), stack.budgets(today));
+    assert_eq!(compacted.budgets(today)[0].spent, 30.0);
+    assert_eq!(compacted.goals(today), stack.goals(today));
+    assert!(compacted.goals(today)[0].projected.is_some());
+    assert!(compacted.due_occurrences(today).is_empty());
+}
+
+#[test]
+fn log_with_includes_is_not_compacted() {
+    let directory = env::temp_dir().join("flapjack_compact_includes");
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    let path = directory.join("log.flap").to_string_lossy().to_string();
+    let archive = directory.join("old.flap");
+    let log = "CREATE Cash\nINCLUDE month.flap current\n";
+    fs::write(&path, log).unwrap();
+    fs::write(directory.join("month.flap"), "INCREMENT Cash 5\n").unwrap();
+
+    let args = [
+        "compact",
+        "--log",
+        &path,
+        "--archive",
+        &archive.to_string_lossy(),
+    ]
+    .iter()
+    .map(|x| x.to_string())
+    .collect::<Vec<String>>();
+    assert!(matches!(
+        cli::run(&args),
+        Err(cli::CliError::CompactWithIncludes(_))
+    ));
+
+    // nothing was archived or rewritten
+    assert!(!archive.exists());
+    assert_eq!(fs::read_to_string(&path).unwrap(), log);
+}
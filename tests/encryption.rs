@@ -0,0 +1,107 @@
+use std::env;
+use std::fs;
+
+use flapjack::encryption::{self, EncryptionError, EncryptionKey};
+use flapjack::flapjack_stack::flapjack::ParseError;
+use flapjack::flapjack_stack::flapjack_stack_builder::FlapJackStackBuilder;
+
+const PASSPHRASE: &str = "correct horse";
+
+#[test]
+fn encrypted_log_is_encrypted_again_on_write() {
+    let path = env::temp_dir().join("flapjack_encrypted.flap");
+    let path_string = path.to_string_lossy().to_string();
+    let key = EncryptionKey::new(PASSPHRASE).unwrap();
+    fs::write(
+        &path,
+        encryption::encrypt("CREATE Cash\nINCREMENT Cash 5\n", &key),
+    )
+    .unwrap();
+
+    let bytes = fs::read(&path).unwrap();
+    assert_eq!(
+        FlapJackStackBuilder::from_bytes(&bytes, Some(path_string.clone()), None).unwrap_err(),
+        EncryptionError::MissingPassphrase
+    );
+
+    let mut stack =
+        FlapJackStackBuilder::from_bytes(&bytes, Some(path_string.clone()), Some(PASSPHRASE))
+            .unwrap()
+            .build()
+            .unwrap();
+    stack
+        .increment_wallet_amount("Cash", 10.0, Some("lunch"))
+        .unwrap();
+
+    // the new line is only readable with the passphrase
+    let written = fs::read(&path).unwrap();
+    assert!(encryption::is_encrypted(&written));
+    assert!(!String::from_utf8_lossy(&written).contains("lunch"));
+
+    let (log, _) = encryption::decrypt(&written, PASSPHRASE).unwrap();
+    assert_eq!(
+        log,
+        "CREATE Cash\nINCREMENT Cash 5\nINCREMENT Cash 10 lunch\n"
+    );
+}
+
+#[test]
+fn encrypted_include_needs_the_passphrase() {
+    let directory = env::temp_dir().join("flapjack_encrypted_include");
+    fs::create_dir_all(&directory).unwrap();
+    let key = EncryptionKey::new(PASSPHRASE).unwrap();
+    fs::write(
+        directory.join("month.flap"),
+        encryption::encrypt("INCREMENT Cash 5\n", &key),
+    )
+    .unwrap();
+
+    let log = "CREATE Cash\nINCLUDE month.flap\n";
+    let log_path = directory.join("main.flap").to_string_lossy().to_string();
+
+    let mut builder =
+        FlapJackStackBuilder::from_bytes(log.as_bytes(), Some(log_path.clone()), Some(PASSPHRASE))
+            .unwrap();
+    let stack = builder.build().unwrap();
+    assert_eq!(stack.db.wallet_amounts.get("Cash"), Some(&5.0));
+    assert!(stack.files[0].key.is_none());
+    assert!(stack.files[1].key.is_some());
+
+    let mut builder =
+        FlapJackStackBuilder::from_bytes(log.as_bytes(), Some(log_path), Some("battery staple"))
+            .unwrap();
+    assert_eq!(
+        builder.build().unwrap_err(),
+        ParseError::CouldNotDecryptInclude {
+            line: 2,
+            path: "month.flap".to_owned(),
+            source: EncryptionError::WrongPassphrase
+        }
+    );
+}
+
+#[cfg(feature = "sqlite")]
+#[test]
+fn encrypted_log_is_only_exported_to_sqlite_when_asked() {
+    let directory = env::temp_dir().join("flapjack_encrypted_sqlite");
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    let key = EncryptionKey::new(PASSPHRASE).unwrap();
+    let log_path = directory.join("log.flap").to_string_lossy().to_string();
+    fs::write(&log_path, encryption::encrypt("CREATE Cash\n", &key)).unwrap();
+    env::set_var("FLAPJACK_PASSPHRASE", PASSPHRASE);
+
+    let run = |extra: &[&str]| {
+        let args = ["sqlite", "--log", &log_path]
+            .iter()
+            .chain(extra)
+            .map(|x| x.to_string())
+            .collect::<Vec<String>>();
+        flapjack::cli::run(&args)
+    };
+    assert!(run(&[]).is_err());
+    assert!(!directory.join("log.sqlite").exists());
+
+    assert_eq!(run(&["--plaintext"]).unwrap(), 0);
+    assert!(directory.join("log.sqlite").exists());
+}
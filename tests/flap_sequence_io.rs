@@ -1,4 +1,4 @@
-use flapjack::flapjack_stack::flapjack::{Command, Comment, Directive, FlapJack};
+use flapjack::flapjack_stack::flapjack::{Comment, Directive, FlapJack};
 use flapjack::flapjack_stack::flapjack_stack_builder::FlapJackStackBuilder;
 use std::env;
 use std::fs;
@@ -9,7 +9,7 @@ const INPUT_FLAP_PATH: &str = "tests/test_files/serialize_deserialize/input.flap
 #[test]
 fn flaps_from_file() {
     let mut builder = FlapJackStackBuilder::from_file(INPUT_FLAP_PATH);
-    let stack = builder.build();
+    let stack = builder.build().unwrap();
 
     assert_eq!(
         stack.flapjacks[0],
@@ -20,29 +20,37 @@ fn flaps_from_file() {
 
     assert_eq!(
         stack.flapjacks[1],
-        FlapJack::Directive(Directive {
-            command: Command::Create,
-            params: vec!["Checking (Bank)".to_owned()]
+        FlapJack::Directive(Directive::Create {
+            wallet: "Checking (Bank)".to_owned(),
+            comment: None,
+            policy: None,
+            meta: Vec::new(),
         })
     );
 
     assert_eq!(
         stack.flapjacks[2],
-        FlapJack::Directive(Directive {
-            command: Command::Create,
-            params: vec!["Savings (Bank)".to_owned()]
+        FlapJack::Directive(Directive::Create {
+            wallet: "Savings (Bank)".to_owned(),
+            comment: None,
+            policy: None,
+            meta: Vec::new(),
         })
     );
 }
 
 #[test]
 fn flaps_to_file() {
-    let stack = FlapJackStackBuilder::from_file(INPUT_FLAP_PATH).build();
+    let stack = FlapJackStackBuilder::from_file(INPUT_FLAP_PATH)
+        .build()
+        .unwrap();
     let serialized = stack.serialize();
     let temp_directory = env::temp_dir();
     let temp_path = temp_directory.join("example_log.flap");
 
-    stack.serialize_to_file(&temp_path.to_string_lossy());
+    stack
+        .serialize_to_file(&temp_path.to_string_lossy())
+        .unwrap();
     let content = fs::read_to_string(&temp_path).unwrap();
     assert_eq!(content, serialized)
 }
@@ -0,0 +1,109 @@
+use std::env;
+use std::fs;
+
+use flapjack::flapjack_stack::flapjack::ParseError;
+use flapjack::flapjack_stack::flapjack_stack_builder::FlapJackStackBuilder;
+use flapjack::flapjack_stack::format::FormatOptions;
+
+// do not write to the input files
+const MAIN_FLAP_PATH: &str = "tests/test_files/include/main.flap";
+
+#[test]
+fn includes_are_read_relative_to_the_log() {
+    let stack = FlapJackStackBuilder::from_file(MAIN_FLAP_PATH)
+        .build()
+        .unwrap();
+
+    assert_eq!(stack.files.len(), 3);
+    assert_eq!(stack.db.wallet_amounts.get("Cash"), Some(&14.5));
+    assert_eq!(stack.db.wallet_amounts.get("Checking (Bank)"), Some(&480.0));
+
+    // the last line of 2026/09.flap
+    let last = stack.origins.iter().rposition(|x| *x == 2).unwrap();
+    assert_eq!(stack.line_number(last), 4);
+
+    // the log itself is written without the included lines
+    let raw = fs::read_to_string(MAIN_FLAP_PATH).unwrap();
+    assert_eq!(stack.serialize(), raw);
+}
+
+#[test]
+fn new_flaps_go_to_the_current_file() {
+    let temp_directory = env::temp_dir().join("flapjack_include");
+    let month_directory = temp_directory.join("2026");
+    fs::create_dir_all(&month_directory).unwrap();
+    for file in ["main.flap", "2026/08.flap", "2026/09.flap"] {
+        fs::copy(
+            format!("tests/test_files/include/{}", file),
+            temp_directory.join(file),
+        )
+        .unwrap();
+    }
+
+    let main_path = temp_directory.join("main.flap");
+    let mut stack = FlapJackStackBuilder::from_file(&main_path.to_string_lossy())
+        .build()
+        .unwrap();
+    stack
+        .increment_wallet_amount("Cash", 10.0, Some("2026-09-20 found it"))
+        .unwrap();
+
+    let september = fs::read_to_string(month_directory.join("09.flap")).unwrap();
+    assert_eq!(
+        september,
+        "DECREMENT Cash 5.50 \"2026-09-02 coffee\"\n\nSET \"Checking (Bank)\" 480 \"2026-09-30 statement\"\nINCREMENT Cash 10 \"2026-09-20 found it\"\n"
+    );
+    let main = fs::read_to_string(&main_path).unwrap();
+    assert_eq!(main, fs::read_to_string(MAIN_FLAP_PATH).unwrap());
+}
+
+#[test]
+fn include_cycles_are_errors() {
+    let error = FlapJackStackBuilder::from_file("tests/test_files/include/cycle/a.flap")
+        .build()
+        .unwrap_err();
+
+    match error {
+        ParseError::InFile { file, source } => {
+            assert!(file.ends_with("b.flap"));
+            assert_eq!(
+                *source,
+                ParseError::IncludeCycle {
+                    line: 1,
+                    path: "a.flap".to_owned()
+                }
+            );
+        }
+        e => panic!("Unexpected error {:?}", e),
+    }
+}
+
+#[test]
+fn errors_name_the_included_file() {
+    let error = FlapJackStackBuilder::from_file("tests/test_files/include/broken/main.flap")
+        .build()
+        .unwrap_err();
+
+    assert_eq!(
+        error.to_string(),
+        "tests/test_files/include/broken/month.flap: Line 3: \"lots\" is not a valid amount."
+    );
+}
+
+#[test]
+fn formatting_keeps_each_file_separate() {
+    let mut stack = FlapJackStackBuilder::from_file(MAIN_FLAP_PATH)
+        .build()
+        .unwrap();
+    stack.format(FormatOptions::default());
+
+    assert_eq!(
+        stack.serialize(),
+        "# wallets are created here, each month has its own file\nCREATE Cash\nCREATE \"Checking (Bank)\"\n\nINCLUDE 2026/08.flap\nINCLUDE 2026/09.flap current\n"
+    );
+    assert_eq!(
+        stack.serialize_file(2),
+        "DECREMENT Cash 5.50 \"2026-09-02 coffee\"\n\nSET \"Checking (Bank)\" 480.00 \"2026-09-30 statement\"\n"
+    );
+    assert_eq!(stack.db.wallet_amounts.get("Cash"), Some(&14.5));
+}
@@ -0,0 +1,37 @@
+#![cfg(feature = "serde")]
+
+use flapjack::flapjack_stack::flapjack::FlapJack;
+use flapjack::flapjack_stack::flapjack_stack_builder::FlapJackStackBuilder;
+use flapjack::flapjack_stack::{FlapJackDb, FlapJackStack};
+
+// do not write to the input path
+const INPUT_FLAP_PATH: &str = "tests/test_files/serialize_deserialize/input.flap";
+
+#[test]
+fn stack_json_round_trip() {
+    let stack = FlapJackStackBuilder::from_file(INPUT_FLAP_PATH)
+        .build()
+        .unwrap();
+
+    let json = serde_json::to_string(&stack).unwrap();
+    let restored: FlapJackStack = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(restored.flapjacks, stack.flapjacks);
+    assert_eq!(restored.db.wallet_amounts, stack.db.wallet_amounts);
+    assert_eq!(restored.serialize(), stack.serialize());
+}
+
+#[test]
+fn db_snapshot_from_flapjacks() {
+    let stack = FlapJackStackBuilder::from_file(INPUT_FLAP_PATH)
+        .build()
+        .unwrap();
+
+    let json = serde_json::to_string(&stack.flapjacks).unwrap();
+    let flapjacks: Vec<FlapJack> = serde_json::from_str(&json).unwrap();
+    let db = FlapJackDb::from_flaps(&flapjacks);
+
+    let db_json = serde_json::to_string(&db).unwrap();
+    let restored: FlapJackDb = serde_json::from_str(&db_json).unwrap();
+    assert_eq!(restored.wallet_amounts, stack.db.wallet_amounts);
+}
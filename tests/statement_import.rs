@@ -0,0 +1,87 @@
+use chrono::NaiveDate;
+use flapjack::flapjack_stack::flapjack::{Amount, Directive, FlapJack};
+use flapjack::flapjack_stack::flapjack_stack_builder::FlapJackStackBuilder;
+use flapjack::import::{self, ImportPreview};
+
+const OFX_PATH: &str = "tests/test_files/import/statement.ofx";
+const QFX_PATH: &str = "tests/test_files/import/statement.qfx";
+const QIF_PATH: &str = "tests/test_files/import/statement.qif";
+
+#[test]
+fn ofx_statement() {
+    let statements = import::parse_statement_file(OFX_PATH).unwrap();
+    assert_eq!(statements.len(), 1);
+    assert_eq!(statements[0].account_id, Some("000123456789".to_owned()));
+
+    let candidates = statements[0].to_candidates("Checking (Bank)");
+    assert_eq!(candidates.len(), 3);
+    assert_eq!(candidates[0].note(), Some("fitid:2026090101".to_owned()));
+
+    assert_eq!(
+        candidates[0].to_flapjack(),
+        FlapJack::Directive(Directive::Increment {
+            wallet: "Checking (Bank)".to_owned(),
+            amount: Amount::new(1200.0),
+            comment: Some("2026-09-01 ACME CORP PAYROLL - Direct deposit".to_owned())
+        })
+    );
+
+    // a memo that repeats the payee is not repeated in the comment
+    assert_eq!(
+        candidates[2].to_flapjack(),
+        FlapJack::Directive(Directive::Decrement {
+            wallet: "Checking (Bank)".to_owned(),
+            amount: Amount::new(62.17),
+            comment: Some("2026-09-03 GROCERY MART".to_owned())
+        })
+    );
+}
+
+#[test]
+fn qfx_statement() {
+    let statements = import::parse_statement_file(QFX_PATH).unwrap();
+    assert_eq!(
+        statements[0].account_id,
+        Some("4111222233334444".to_owned())
+    );
+
+    let transactions = &statements[0].transactions;
+    assert_eq!(transactions.len(), 2);
+    assert_eq!(transactions[0].amount, -59.99);
+    assert_eq!(transactions[1].date, NaiveDate::from_ymd_opt(2026, 9, 12));
+}
+
+#[test]
+fn qif_matches_ofx() {
+    let ofx = import::parse_statement_file(OFX_PATH).unwrap();
+    let qif = import::parse_statement_file(QIF_PATH).unwrap();
+
+    assert_eq!(qif[0].account_id, None);
+    let flapjacks = |statement: &import::Statement| {
+        statement
+            .to_candidates("Checking (Bank)")
+            .iter()
+            .map(|x| x.to_flapjack())
+            .collect::<Vec<FlapJack>>()
+    };
+    assert_eq!(flapjacks(&ofx[0]), flapjacks(&qif[0]));
+    assert!(qif[0].transactions.iter().all(|x| x.id.is_none()));
+}
+
+#[test]
+fn reimport_is_flagged_as_duplicate() {
+    let statements = import::parse_statement_file(OFX_PATH).unwrap();
+    let candidates = statements[0].to_candidates("Checking (Bank)");
+
+    let mut log = String::from("CREATE \"Checking (Bank)\"");
+    for candidate in &candidates[..2] {
+        log.push('\n');
+        log.push_str(&candidate.to_flapjack().serialize());
+    }
+
+    let stack = FlapJackStackBuilder::new(&log, None).build().unwrap();
+    let preview = ImportPreview::new(candidates, &stack);
+
+    assert_eq!(preview.duplicate_count(), 2);
+    assert_eq!(preview.included().count(), 1);
+}